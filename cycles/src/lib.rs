@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Detects the cycle reached by repeatedly applying `f` to a starting state.
+// The sequence start, f(start), f(f(start)), ... runs through `tail` states
+// before entering a loop of `period` states.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    Floyd,
+    Brent,
+    Hash,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cycle {
+    pub tail: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    // The number of steps from the start which reach the same state as `n`
+    // steps would, without going round the cycle more than once.
+    pub fn equivalent_step(&self, n: usize) -> usize {
+        if n < self.tail { n } else { self.tail + (n - self.tail) % self.period }
+    }
+
+    pub fn state_at<S: Clone, F: Fn(&S) -> S>(&self, start: &S, f: F, n: usize) -> S {
+        iterate(start, &f, self.equivalent_step(n))
+    }
+}
+
+fn iterate<S: Clone, F: Fn(&S) -> S>(start: &S, f: &F, steps: usize) -> S {
+    let mut current = start.clone();
+    for _ in 0..steps {
+        current = f(&current);
+    }
+    current
+}
+
+// Once `hare` is `period` steps ahead of `tortoise`, walking both forwards from
+// the start makes them meet at the first state on the cycle.
+fn tail_length<S: Clone + PartialEq, F: Fn(&S) -> S>(start: &S, f: &F, period: usize) -> usize {
+    let mut tortoise = start.clone();
+    let mut hare = iterate(start, f, period);
+    let mut tail = 0;
+    while tortoise != hare {
+        tortoise = f(&tortoise);
+        hare = f(&hare);
+        tail += 1;
+    }
+    tail
+}
+
+pub fn floyd<S: Clone + PartialEq, F: Fn(&S) -> S>(start: &S, f: F) -> Cycle {
+    let mut tortoise = f(start);
+    let mut hare = f(&tortoise);
+    while tortoise != hare {
+        tortoise = f(&tortoise);
+        hare = f(&f(&hare));
+    }
+
+    let mut period = 1;
+    hare = f(&tortoise);
+    while tortoise != hare {
+        hare = f(&hare);
+        period += 1;
+    }
+
+    Cycle { tail: tail_length(start, &f, period), period }
+}
+
+pub fn brent<S: Clone + PartialEq, F: Fn(&S) -> S>(start: &S, f: F) -> Cycle {
+    let (mut power, mut period) = (1, 1);
+    let mut tortoise = start.clone();
+    let mut hare = f(start);
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = f(&hare);
+        period += 1;
+    }
+
+    Cycle { tail: tail_length(start, &f, period), period }
+}
+
+pub fn hashed<S: Clone + Eq + Hash, F: Fn(&S) -> S>(start: &S, f: F) -> Cycle {
+    let mut seen = HashMap::new();
+    let mut current = start.clone();
+    let mut step = 0;
+    loop {
+        if let Some(&first_seen) = seen.get(&current) {
+            return Cycle { tail: first_seen, period: step - first_seen };
+        }
+        let next = f(&current);
+        seen.insert(current, step);
+        current = next;
+        step += 1;
+    }
+}
+
+pub fn detect<S: Clone + Eq + Hash, F: Fn(&S) -> S>(strategy: Strategy, start: &S, f: F) -> Cycle {
+    match strategy {
+        Strategy::Floyd => floyd(start, f),
+        Strategy::Brent => brent(start, f),
+        Strategy::Hash => hashed(start, f),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRATEGIES: [Strategy; 3] = [Strategy::Floyd, Strategy::Brent, Strategy::Hash];
+
+    fn detect_all<S: Clone + Eq + Hash, F: Fn(&S) -> S>(start: &S, f: F) -> Cycle {
+        let cycles: Vec<_> = STRATEGIES.iter().map(|&strategy| detect(strategy, start, &f)).collect();
+        assert!(cycles.iter().all(|&cycle| cycle == cycles[0]), "Strategies disagree: {:?}", cycles);
+        cycles[0]
+    }
+
+    // The memory banks example from day 6.
+    fn redistribute(banks: &[u32]) -> Vec<u32> {
+        let mut banks = banks.to_vec();
+        let (mut idx, mut blocks) = banks.iter().cloned().enumerate().rev().max_by_key(|&(_, blocks)| blocks).unwrap();
+        banks[idx] = 0;
+        while blocks > 0 {
+            idx = (idx + 1) % banks.len();
+            banks[idx] += 1;
+            blocks -= 1;
+        }
+        banks
+    }
+
+    #[test]
+    fn finds_the_tail_and_period() {
+        assert_eq!(detect_all(&0u32, |&x| x), Cycle { tail: 0, period: 1 });
+        assert_eq!(detect_all(&0u32, |&x| (x + 1) % 7), Cycle { tail: 0, period: 7 });
+        assert_eq!(detect_all(&0u32, |&x| if x < 10 { x + 1 } else { 7 }), Cycle { tail: 7, period: 4 });
+        assert_eq!(detect_all(&vec![0, 2, 7, 0], |banks: &Vec<u32>| redistribute(banks)), Cycle { tail: 1, period: 4 });
+    }
+
+    #[test]
+    fn strategies_agree_on_random_functions() {
+        // Each seed gives a random function on 0..size, from xorshift.
+        for seed in 1..300u64 {
+            let size = seed % 40 + 1;
+            let f = |&x: &u64| {
+                let mut z = x ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                z ^= z << 13;
+                z ^= z >> 7;
+                z ^= z << 17;
+                z % size
+            };
+            let cycle = detect_all(&0, f);
+            let first_repeat = iterate(&0, &f, cycle.tail + cycle.period);
+            assert_eq!(first_repeat, iterate(&0, &f, cycle.tail), "Wrong cycle for seed {}", seed);
+            if cycle.tail > 0 {
+                assert_ne!(iterate(&0, &f, cycle.tail - 1), iterate(&0, &f, cycle.tail - 1 + cycle.period));
+            }
+        }
+    }
+
+    #[test]
+    fn jumps_to_the_equivalent_step() {
+        let f = |&x: &u32| if x < 10 { x + 1 } else { 7 };
+        let cycle = Cycle { tail: 7, period: 4 };
+        assert_eq!(cycle.equivalent_step(3), 3);
+        assert_eq!(cycle.equivalent_step(7), 7);
+        assert_eq!(cycle.equivalent_step(12), 8);
+        assert_eq!(cycle.equivalent_step(1_000_000_000), 8);
+        for n in 0..50 {
+            assert_eq!(cycle.state_at(&0, f, n), iterate(&0, &f, n), "Wrong state after {} steps", n);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+use day10::digest::Digest;
+use day10::get_digest;
+use day10::knot;
+
+// The original approach: rotate the start of a wrapped section to the front
+// of the ring, reverse it there, then rotate the ring back. Each wrapped
+// reversal moves every element twice.
+fn reverse_section_by_rotation(slice: &mut [u8], start: usize, end: usize) {
+    if start < end {
+        slice[start..end + 1].reverse();
+    } else if start > end {
+        let reverse_len = (slice.len() - start) + end;
+        slice.rotate_left(start);
+        slice[0..reverse_len + 1].reverse();
+        slice.rotate_left(reverse_len - end);
+    }
+}
+
+fn hash_by_rotation(bytes: &[u8]) -> Digest {
+    let mut knot: Vec<_> = (0..=255).collect();
+    let mut lengths = bytes.to_vec();
+    lengths.extend_from_slice(&knot::DEFAULT_SUFFIX);
+
+    let mut skip_size = 0;
+    let mut start: usize = 0;
+    for _ in 0..knot::DEFAULT_ROUNDS {
+        for &length in &lengths {
+            if length != 0 {
+                let end = (start + length as usize - 1) % knot.len();
+                reverse_section_by_rotation(&mut knot, start, end);
+            }
+            start = (start + length as usize + skip_size) % knot.len();
+            skip_size += 1;
+        }
+    }
+    Digest::new(knot.chunks(knot::DEFAULT_BLOCK_SIZE)
+        .map(|chunk| chunk.iter().fold(0, |acc, curr| acc ^ curr))
+        .collect())
+}
+
+fn time<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+// Hashes inputs like the rows of a day 14 grid, in batches of 128 per key.
+pub fn run(hash_count: usize) {
+    let inputs: Vec<String> = (0..hash_count).map(|n| format!("key{}-{}", n / 128, n % 128)).collect();
+
+    let (before, before_time) = time(|| inputs.iter().map(|input| hash_by_rotation(input.as_bytes())).collect::<Vec<_>>());
+    println!("Rotating the ring: {} hashes in {:.3}s ({:.0} hashes per second)",
+             hash_count, seconds(before_time), hash_count as f64 / seconds(before_time));
+
+    let (after, after_time) = time(|| inputs.iter().map(|input| get_digest(input.as_bytes())).collect::<Vec<_>>());
+    println!("Fixed ring: {} hashes in {:.3}s ({:.0} hashes per second, {:.1}x faster)",
+             hash_count, seconds(after_time), hash_count as f64 / seconds(after_time),
+             seconds(before_time) / seconds(after_time));
+
+    assert!(before == after, "The fixed ring gives different hashes from rotating the ring");
+}
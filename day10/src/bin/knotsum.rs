@@ -0,0 +1,183 @@
+extern crate day10;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader};
+use std::process;
+use day10::get_hash_with;
+use day10::knot::KnotHasher;
+
+// Prints or checks knot hashes of files, in the same format as sha256sum:
+//
+//     knotsum [--rounds N] [--size N] [FILE]...
+//     knotsum [--rounds N] [--size N] -c [FILE]...
+//
+// With no files, or when a file is `-`, standard input is read. Checking
+// reads lines of `HASH  FILENAME` from each file and hashes the files they
+// name, so a list must be checked with the same settings that made it.
+
+struct Options {
+    hasher: KnotHasher,
+    check: bool,
+    paths: Vec<String>,
+}
+
+fn options_from_args() -> Options {
+    let mut options = Options { hasher: KnotHasher::new(), check: false, paths: Vec::new() };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--check" => options.check = true,
+            "--rounds" => {
+                let rounds = args.next().and_then(|n| n.parse().ok()).expect("--rounds expects a number of rounds");
+                options.hasher = options.hasher.rounds(rounds);
+            },
+            "--size" => {
+                let size = args.next().and_then(|n| n.parse().ok())
+                    .filter(|&size: &usize| size.is_multiple_of(16) && (16..=256).contains(&size))
+                    .expect("--size expects a ring size which is a multiple of 16, up to 256");
+                options.hasher = options.hasher.ring_size(size);
+            },
+            _ if arg.starts_with('-') && arg != "-" => panic!("Unrecognised argument {}", arg),
+            _ => options.paths.push(arg),
+        }
+    }
+    if options.paths.is_empty() {
+        options.paths.push("-".to_string());
+    }
+    options
+}
+
+fn read_all(path: &str) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    if path == "-" {
+        let stdin = io::stdin();
+        stdin.lock().read_to_end(&mut bytes)?;
+    } else {
+        File::open(path)?.read_to_end(&mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+fn open_lines(path: &str) -> io::Result<Vec<String>> {
+    if path == "-" {
+        io::stdin().lock().lines().collect()
+    } else {
+        BufReader::new(File::open(path)?).lines().collect()
+    }
+}
+
+// Splits a line of a checksum list into its hash and file name. Like
+// sha256sum, a `*` in place of the second space marks a binary file, which
+// makes no difference here.
+fn parse_check_line(line: &str) -> Option<(&str, &str)> {
+    let space = line.find(' ')?;
+    let (hash, rest) = (&line[..space], &line[space + 1..]);
+    let name = if rest.starts_with(' ') || rest.starts_with('*') { &rest[1..] } else { return None };
+    if hash.is_empty() || name.is_empty() || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some((hash, name))
+}
+
+// Returns whether every file could be read.
+fn print_hashes(options: &Options) -> bool {
+    let mut ok = true;
+    for path in &options.paths {
+        match read_all(path) {
+            Ok(bytes) => println!("{}  {}", get_hash_with(&options.hasher, &bytes), path),
+            Err(e) => {
+                eprintln!("knotsum: {}: {}", path, e);
+                ok = false;
+            },
+        }
+    }
+    ok
+}
+
+// Returns whether every listed file was read and matched its hash. As with
+// sha256sum, a list with no properly formatted lines at all is a failure.
+fn check_hashes(options: &Options) -> bool {
+    let (mut malformed, mut unreadable, mut mismatched, mut empty_lists) = (0, 0, 0, 0);
+    for list in &options.paths {
+        let lines = match open_lines(list) {
+            Ok(lines) => lines,
+            Err(e) => {
+                eprintln!("knotsum: {}: {}", list, e);
+                unreadable += 1;
+                continue;
+            },
+        };
+
+        let mut entries = 0;
+        for line in lines.iter().filter(|line| !line.is_empty()) {
+            let (expected, path) = match parse_check_line(line) {
+                Some(entry) => entry,
+                None => {
+                    malformed += 1;
+                    continue;
+                },
+            };
+            entries += 1;
+            match read_all(path) {
+                Ok(bytes) if get_hash_with(&options.hasher, &bytes).eq_ignore_ascii_case(expected) => println!("{}: OK", path),
+                Ok(_) => {
+                    println!("{}: FAILED", path);
+                    mismatched += 1;
+                },
+                Err(e) => {
+                    eprintln!("knotsum: {}: {}", path, e);
+                    println!("{}: FAILED open or read", path);
+                    unreadable += 1;
+                },
+            }
+        }
+
+        if entries == 0 {
+            eprintln!("knotsum: {}: no properly formatted checksum lines found", list);
+            empty_lists += 1;
+        }
+    }
+
+    if malformed > 0 {
+        eprintln!("knotsum: WARNING: {} line{} improperly formatted", malformed, if malformed == 1 { " is" } else { "s are" });
+    }
+    if unreadable > 0 {
+        eprintln!("knotsum: WARNING: {} listed file{} could not be read", unreadable, if unreadable == 1 { "" } else { "s" });
+    }
+    if mismatched > 0 {
+        eprintln!("knotsum: WARNING: {} computed checksum{} did NOT match", mismatched, if mismatched == 1 { "" } else { "s" });
+    }
+    unreadable == 0 && mismatched == 0 && empty_lists == 0
+}
+
+fn main() {
+    let options = options_from_args();
+    let ok = if options.check { check_hashes(&options) } else { print_hashes(&options) };
+    if !ok {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_check_lines() {
+        assert_eq!(parse_check_line("28e7c436  inputs/input.txt"), Some(("28e7c436", "inputs/input.txt")));
+        assert_eq!(parse_check_line("28E7C436 *input.bin"), Some(("28E7C436", "input.bin")));
+        assert_eq!(parse_check_line("28e7c436  name with  spaces"), Some(("28e7c436", "name with  spaces")));
+        assert_eq!(parse_check_line("28e7c436   leading space"), Some(("28e7c436", " leading space")));
+    }
+
+    #[test]
+    fn rejects_malformed_check_lines() {
+        assert_eq!(parse_check_line("28e7c436 input.txt"), None);
+        assert_eq!(parse_check_line("28e7c436"), None);
+        assert_eq!(parse_check_line("28e7c436  "), None);
+        assert_eq!(parse_check_line("  input.txt"), None);
+        assert_eq!(parse_check_line("28e7g436  input.txt"), None);
+        assert_eq!(parse_check_line("not a checksum line"), None);
+    }
+}
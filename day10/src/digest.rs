@@ -0,0 +1,187 @@
+use std::fmt;
+use std::str::FromStr;
+
+const HEX_LOWER: &[u8] = b"0123456789abcdef";
+const HEX_UPPER: &[u8] = b"0123456789ABCDEF";
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// The raw bytes of a knot hash: 16 of them with the usual settings, or one
+// per block of the ring otherwise.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Digest {
+    bytes: Vec<u8>,
+}
+
+impl Digest {
+    pub fn new(bytes: Vec<u8>) -> Digest {
+        Digest { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn to_hex_with(&self, chars: &[u8]) -> String {
+        let mut result = String::with_capacity(2 * self.bytes.len());
+        for &byte in &self.bytes {
+            result.push(chars[(byte >> 4) as usize] as char);
+            result.push(chars[(byte & 0xf) as usize] as char);
+        }
+        result
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.to_hex_with(HEX_LOWER)
+    }
+
+    pub fn to_upper_hex(&self) -> String {
+        self.to_hex_with(HEX_UPPER)
+    }
+
+    // Eight digits per byte, most significant bit first, so there is always
+    // a digit for every bit of the digest.
+    pub fn to_binary(&self) -> String {
+        self.bytes.iter().map(|byte| format!("{:08b}", byte)).collect()
+    }
+
+    // Standard base64, padded with `=` to a multiple of four characters.
+    pub fn to_base64(&self) -> String {
+        let mut result = String::new();
+        for chunk in self.bytes.chunks(3) {
+            let bits = chunk.iter().enumerate().fold(0u32, |acc, (idx, &byte)| acc | (byte as u32) << (16 - 8 * idx));
+            for idx in 0..4 {
+                if idx <= chunk.len() {
+                    result.push(BASE64[(bits >> (18 - 6 * idx) & 0x3f) as usize] as char);
+                } else {
+                    result.push('=');
+                }
+            }
+        }
+        result
+    }
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+// Parses hex in either case, two digits to a byte.
+impl FromStr for Digest {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Digest, String> {
+        if s.is_empty() || !s.len().is_multiple_of(2) {
+            return Err(format!("A digest needs an even number of hex digits, not {}", s.len()));
+        }
+        s.as_bytes().chunks(2)
+            .map(|pair| match (hex_value(pair[0]), hex_value(pair[1])) {
+                (Some(hi), Some(lo)) => Ok(hi << 4 | lo),
+                _ => Err(format!("'{}' is not a hex byte", String::from_utf8_lossy(pair))),
+            })
+            .collect::<Result<Vec<u8>, String>>()
+            .map(Digest::new)
+    }
+}
+
+// The formatting traits put `0x` or `0b` in front when asked with `#`, and
+// respect the width and fill like the integer implementations do.
+impl fmt::LowerHex for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "0x", &self.to_hex())
+    }
+}
+
+impl fmt::UpperHex for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "0x", &self.to_upper_hex())
+    }
+}
+
+impl fmt::Binary for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad_integral(true, "0b", &self.to_binary())
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(&self.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use get_digest;
+
+    // Starts with a zero byte, so that dropping leading zeros would show.
+    fn sample() -> Digest {
+        Digest::new(vec![0x00, 0x0f, 0x10, 0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67, 0x89, 0x9a, 0xbc, 0xde, 0xf0, 0xff])
+    }
+
+    #[test]
+    fn round_trips_through_hex() {
+        let digest = sample();
+        assert_eq!(digest.to_hex(), "000f10abcdef01234567899abcdef0ff");
+        assert_eq!(digest.to_upper_hex(), "000F10ABCDEF01234567899ABCDEF0FF");
+        assert_eq!(format!("{:x}", digest), digest.to_hex());
+        assert_eq!(format!("{:X}", digest), digest.to_upper_hex());
+        assert_eq!(format!("{}", digest), digest.to_hex());
+        for hex in &[digest.to_hex(), digest.to_upper_hex(), format!("{:x}", digest), format!("{:X}", digest)] {
+            assert_eq!(hex.parse::<Digest>(), Ok(digest.clone()), "{} does not parse back to the digest", hex);
+        }
+        assert_eq!("aBcD".parse::<Digest>(), Ok(Digest::new(vec![0xab, 0xcd])));
+    }
+
+    #[test]
+    fn pads_and_prefixes_like_integers() {
+        let digest = Digest::new(vec![0x0a, 0xbc]);
+        assert_eq!(format!("{:#x}", digest), "0x0abc");
+        assert_eq!(format!("{:#X}", digest), "0x0ABC");
+        assert_eq!(format!("{:#b}", digest), "0b0000101010111100");
+        assert_eq!(format!("{:>8x}", digest), "    0abc");
+        assert_eq!(format!("{:08x}", digest), "00000abc");
+        assert_eq!(format!("{:<6}|", digest), "0abc  |");
+    }
+
+    #[test]
+    fn rejects_malformed_hex() {
+        assert!("".parse::<Digest>().is_err());
+        assert_eq!("abc".parse::<Digest>(), Err("A digest needs an even number of hex digits, not 3".to_string()));
+        assert_eq!("abzz".parse::<Digest>(), Err("'zz' is not a hex byte".to_string()));
+        assert_eq!("0x00".parse::<Digest>(), Err("'0x' is not a hex byte".to_string()));
+        assert!("ab c".parse::<Digest>().is_err());
+        assert!("é0".parse::<Digest>().is_err());
+    }
+
+    #[test]
+    fn writes_every_bit_in_binary() {
+        let binary = sample().to_binary();
+        assert_eq!(binary.len(), 128);
+        assert!(binary.starts_with("000000000000111100010000"));
+        assert!(binary.ends_with("1111000011111111"));
+        assert_eq!(format!("{:b}", sample()), binary);
+    }
+
+    #[test]
+    fn pads_base64_to_whole_quanta() {
+        assert_eq!(Digest::new(b"Man".to_vec()).to_base64(), "TWFu");
+        assert_eq!(Digest::new(b"Ma".to_vec()).to_base64(), "TWE=");
+        assert_eq!(Digest::new(b"M".to_vec()).to_base64(), "TQ==");
+        assert_eq!(Digest::new(vec![0xfb, 0xff]).to_base64(), "+/8=");
+        assert_eq!(Digest::new(vec![]).to_base64(), "");
+        assert_eq!(sample().to_base64(), "AA8Qq83vASNFZ4mavN7w/w==");
+    }
+
+    #[test]
+    fn matches_the_known_hashes() {
+        assert_eq!(get_digest(b"").to_hex(), "a2582a3a0e66e6e86e3812dcb672a272");
+        assert_eq!(get_digest(b"AoC 2017").to_hex(), "33efeb34ea91902bb2f59c9920caa6cd");
+        assert_eq!(get_digest(include_bytes!("../inputs/input.txt")).to_hex(), "28e7c4360520718a5dc811d3942cf1fd");
+    }
+}
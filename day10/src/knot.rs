@@ -0,0 +1,214 @@
+use std::hash::{BuildHasher, Hasher};
+use digest::Digest;
+
+// Reverses `length` elements of the ring starting at `start`, wrapping round
+// the end of the slice if need be. The ring stays where it is, and only the
+// elements being reversed are moved.
+fn reverse_section(slice: &mut [u8], start: usize, length: usize) {
+    let knot_length = slice.len();
+    if start + length <= knot_length {
+        slice[start..start + length].reverse();
+        return;
+    }
+
+    // A wrapped section is a tail, from `start` to the end of the slice,
+    // followed by a head at the front. They are gathered into a buffer,
+    // reversed together there and scattered back. A knot holds distinct
+    // bytes, so it never has more than 256 elements.
+    let mut buffer = [0u8; 256];
+    let (tail, head) = (knot_length - start, start + length - knot_length);
+    buffer[..tail].copy_from_slice(&slice[start..]);
+    buffer[tail..length].copy_from_slice(&slice[..head]);
+    buffer[..length].reverse();
+    slice[start..].copy_from_slice(&buffer[..tail]);
+    slice[..head].copy_from_slice(&buffer[tail..length]);
+}
+
+pub fn apply_hashing_rounds(slice: &mut [u8], lengths: &[u8], rounds: usize) {
+    let mut skip_size = 0;
+    let mut start: usize = 0;
+    let knot_length = slice.len();
+
+    for _ in 0..rounds {
+        for &length in lengths {
+            if length as usize <= knot_length {
+                reverse_section(slice, start, length as usize);
+            }
+            start = (start + length as usize + skip_size) % knot_length;
+            skip_size += 1;
+        }
+    }
+}
+
+pub const DEFAULT_RING_SIZE: usize = 256;
+pub const DEFAULT_ROUNDS: usize = 64;
+pub const DEFAULT_SUFFIX: [u8; 5] = [17, 31, 73, 47, 23];
+pub const DEFAULT_BLOCK_SIZE: usize = 16;
+
+// Builds up a knot hash of everything passed to `update`. Every round runs
+// over the whole input, so it is buffered until `finalize`.
+//
+// With the default settings this is the hash from the puzzle, and the builder
+// methods change them. A hasher with no input doubles as a `BuildHasher`, so
+// that `HashMap`s can be keyed by knot hashes with any settings.
+#[derive(Clone, Debug)]
+pub struct KnotHasher {
+    ring_size: usize,
+    rounds: usize,
+    suffix: Vec<u8>,
+    block_size: usize,
+    input: Vec<u8>,
+}
+
+impl KnotHasher {
+    pub fn new() -> KnotHasher {
+        KnotHasher {
+            ring_size: DEFAULT_RING_SIZE,
+            rounds: DEFAULT_ROUNDS,
+            suffix: DEFAULT_SUFFIX.to_vec(),
+            block_size: DEFAULT_BLOCK_SIZE,
+            input: Vec::new(),
+        }
+    }
+
+    // The ring holds each byte from 0 up to `ring_size - 1`, so there can be
+    // at most 256 elements. Lengths longer than the ring move the position on
+    // without reversing anything.
+    pub fn ring_size(mut self, ring_size: usize) -> KnotHasher {
+        assert!((1..=256).contains(&ring_size), "The ring size must be between 1 and 256");
+        self.ring_size = ring_size;
+        self
+    }
+
+    pub fn rounds(mut self, rounds: usize) -> KnotHasher {
+        self.rounds = rounds;
+        self
+    }
+
+    pub fn suffix(mut self, suffix: &[u8]) -> KnotHasher {
+        self.suffix = suffix.to_vec();
+        self
+    }
+
+    // Each block of the ring is XORed down to one byte of the digest, so the
+    // block size must divide the ring size by the time the hash is finalized.
+    pub fn block_size(mut self, block_size: usize) -> KnotHasher {
+        assert!(block_size >= 1, "The block size must be at least 1");
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.input.extend_from_slice(bytes);
+    }
+
+    pub fn reset(&mut self) {
+        self.input.clear();
+    }
+
+    // Hashes the input so far, leaving it in place so that more can be added.
+    pub fn finalize(&self) -> Digest {
+        assert!(self.ring_size.is_multiple_of(self.block_size), "The block size must divide the ring size");
+        let mut knot: Vec<_> = (0..self.ring_size).map(|x| x as u8).collect();
+        let mut lengths = self.input.clone();
+        lengths.extend_from_slice(&self.suffix);
+
+        apply_hashing_rounds(&mut knot, &lengths, self.rounds);
+        Digest::new(knot.chunks(self.block_size)
+            .map(|chunk| chunk.iter().fold(0, |acc, curr| acc ^ curr))
+            .collect())
+    }
+}
+
+impl Default for KnotHasher {
+    fn default() -> KnotHasher {
+        KnotHasher::new()
+    }
+}
+
+impl Hasher for KnotHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    // Folds the digest into eight bytes, XORing together the bytes which
+    // share a position modulo eight.
+    fn finish(&self) -> u64 {
+        let mut folded = [0u8; 8];
+        for (idx, byte) in self.finalize().as_bytes().iter().enumerate() {
+            folded[idx % 8] ^= byte;
+        }
+        folded.iter().fold(0, |acc, &byte| acc << 8 | byte as u64)
+    }
+}
+
+impl BuildHasher for KnotHasher {
+    type Hasher = KnotHasher;
+
+    fn build_hasher(&self) -> KnotHasher {
+        let mut hasher = self.clone();
+        hasher.reset();
+        hasher
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn ties_the_example_knot() {
+        // The third length wraps round the end of the ring.
+        let mut hasher = KnotHasher::new().ring_size(5).rounds(1).suffix(&[]).block_size(1);
+        hasher.update(&[3, 4, 1, 5]);
+        let digest = hasher.finalize();
+        assert_eq!(digest.as_bytes(), &[3, 4, 2, 1, 0]);
+        assert_eq!(digest.as_bytes()[0] as usize * digest.as_bytes()[1] as usize, 12);
+    }
+
+    #[test]
+    fn reverses_wrapped_sections_in_place() {
+        let mut ring = [0, 1, 2, 3, 4, 5, 6];
+        reverse_section(&mut ring, 5, 4);
+        assert_eq!(ring, [6, 5, 2, 3, 4, 1, 0]);
+        reverse_section(&mut ring, 6, 7);
+        assert_eq!(ring, [4, 3, 2, 5, 6, 0, 1]);
+    }
+
+    #[test]
+    fn hashes_the_same_however_the_input_is_split() {
+        let mut whole = KnotHasher::new();
+        whole.update(b"AoC 2017");
+        let mut split = KnotHasher::new();
+        split.update(b"AoC");
+        split.update(b" 2017");
+        assert_eq!(split.finalize(), whole.finalize());
+        assert_eq!(whole.finalize().to_hex(), "33efeb34ea91902bb2f59c9920caa6cd");
+    }
+
+    #[test]
+    fn builds_hashers_with_no_input() {
+        let mut used = KnotHasher::new().rounds(8);
+        used.update(b"left over");
+        let built = used.build_hasher();
+        assert_eq!(built.finalize(), KnotHasher::new().rounds(8).finalize());
+        assert_eq!(used.finalize(), {
+            let mut fresh = KnotHasher::new().rounds(8);
+            fresh.update(b"left over");
+            fresh.finalize()
+        });
+    }
+
+    #[test]
+    fn keys_hash_maps() {
+        let mut counts = HashMap::with_hasher(KnotHasher::new());
+        for word in "the knot the hash the end".split(' ') {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts["the"], 3);
+        assert_eq!(counts["knot"], 1);
+        assert!(!counts.contains_key("tie"));
+    }
+}
@@ -0,0 +1,23 @@
+pub mod digest;
+pub mod knot;
+
+use digest::Digest;
+use knot::KnotHasher;
+
+pub fn get_digest_with(hasher: &KnotHasher, bytes: &[u8]) -> Digest {
+    let mut hasher = hasher.clone();
+    hasher.update(bytes);
+    hasher.finalize()
+}
+
+pub fn get_digest(bytes: &[u8]) -> Digest {
+    get_digest_with(&KnotHasher::new(), bytes)
+}
+
+pub fn get_hash_with(hasher: &KnotHasher, bytes: &[u8]) -> String {
+    get_digest_with(hasher, bytes).to_hex()
+}
+
+pub fn get_hash(bytes: &[u8]) -> String {
+    get_digest(bytes).to_hex()
+}
@@ -1,9 +1,13 @@
-#![feature(slice_rotate)]
-#![feature(inclusive_range_syntax)]
+extern crate day10;
 
+mod benchmark;
+
+use std::env;
 use std::fs::File;
 use std::io::{BufReader, BufRead, Read};
-use std::str::from_utf8;
+use std::str::{from_utf8, FromStr};
+use day10::{get_digest_with, get_hash, get_hash_with, knot};
+use day10::knot::KnotHasher;
 
 const INPUT_PATH: &'static str = "inputs/input.txt";
 
@@ -25,64 +29,89 @@ fn get_bytes_from_file(path: &str) -> Vec<u8> {
     buf
 }
 
-fn reverse_section(slice: &mut [u8], start: usize, end: usize) {
-    if start < end {
-        slice[start..end + 1].reverse();
-    } else if start > end {
-        let reverse_len = (slice.len() - start) + end;
-        slice.rotate(start);
-        slice[0..reverse_len + 1].reverse();
-        slice.rotate(reverse_len - end);
-    }
+struct Options {
+    input_path: String,
+    hasher: KnotHasher,
+    custom_hash: bool,
+    benchmark_hashes: Option<usize>,
+    show_encodings: bool,
 }
 
-fn apply_hashing_rounds(slice: &mut [u8], lengths: &[u8], rounds: usize) {
-    let mut skip_size = 0;
-    let mut start: usize = 0;
-    let knot_length = slice.len();
+fn parse_arg<T: FromStr>(arg: Option<String>, message: &str) -> T {
+    arg.and_then(|n| n.parse().ok()).expect(message)
+}
 
-    for _ in 0..rounds {
-        for &length in lengths {
-            if length != 0 {
-                let end = (start + length as usize - 1) % knot_length;
-                reverse_section(slice, start, end);
-            }
-            start = (start + length as usize + skip_size) % knot_length;
-            skip_size += 1;
+fn options_from_args() -> Options {
+    let mut options = Options { input_path: INPUT_PATH.to_string(), hasher: KnotHasher::new(), custom_hash: false, benchmark_hashes: None, show_encodings: false };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => options.input_path = args.next().expect("--input expects a path"),
+            "--size" => options.hasher = options.hasher.ring_size(parse_arg(args.next(), "--size expects a ring size from 1 to 256")),
+            "--rounds" => options.hasher = options.hasher.rounds(parse_arg(args.next(), "--rounds expects a number of rounds")),
+            "--suffix" => {
+                let suffix = args.next().expect("--suffix expects comma-separated lengths");
+                let lengths: Vec<u8> = suffix.split(',')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| parse_arg(Some(s.to_string()), "--suffix expects comma-separated lengths"))
+                    .collect();
+                options.hasher = options.hasher.suffix(&lengths);
+            },
+            "--block-size" => options.hasher = options.hasher.block_size(parse_arg(args.next(), "--block-size expects a block size")),
+            "--encodings" => {
+                options.show_encodings = true;
+                continue;
+            },
+            "--benchmark" => {
+                options.benchmark_hashes = Some(parse_arg(args.next(), "--benchmark expects a number of hashes"));
+                continue;
+            },
+            _ => panic!("Unrecognised argument {}", arg),
         }
+        options.custom_hash |= arg != "--input";
     }
+    options
 }
 
-fn as_hex(byte: u8) -> String {
-    static CHARS: &'static [&str] = &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "a", "b", "c", "d", "e", "f"];
-    let (hi, lo) = (byte / 16, byte % 16);
-    CHARS[hi as usize].to_string() + CHARS[lo as usize]
+fn part1(options: &Options) {
+    let mut knot: Vec<_> = (0..=255).collect();
+    let lengths = get_lengths_from_file(&options.input_path);
+    knot::apply_hashing_rounds(&mut knot, &lengths, 1);
+    println!("The answer to Part 1 is {}", knot[0] as usize * knot[1] as usize);
 }
 
-fn get_hash(bytes: &[u8]) -> String {
-    let mut twisted_knot: Vec<_> = (0..=255).collect();
-    let mut padded_lengths = bytes.to_vec();
-    padded_lengths.extend_from_slice(&[17, 31, 73, 47, 23]);
-
-    apply_hashing_rounds(&mut twisted_knot, &padded_lengths, 64);
-    twisted_knot.chunks(16)
-        .map(|chunk| chunk.iter().fold(0, |acc, curr| acc ^ curr))
-        .fold(String::new(), |acc, curr| acc + &as_hex(curr))
+fn part2(options: &Options) {
+    let bytes = get_bytes_from_file(&options.input_path);
+    println!("The answer to Part 2 is {}", get_hash(&bytes));
 }
 
-fn part1() {
-    let mut knot: Vec<_> = (0..=255).collect();
-    let lengths = get_lengths_from_file(INPUT_PATH);
-    apply_hashing_rounds(&mut knot, &lengths, 1);
-    println!("The answer to Part 1 is {}", knot[0] as usize * knot[1] as usize);
+fn custom_hash(options: &Options) {
+    let bytes = get_bytes_from_file(&options.input_path);
+    println!("With the custom settings the hash is {}", get_hash_with(&options.hasher, &bytes));
 }
 
-fn part2() {
-    let bytes = get_bytes_from_file(INPUT_PATH);
-    println!("The answer to Part 2 is {}", get_hash(&bytes));
+// Prints the digest in every encoding.
+fn show_encodings(options: &Options) {
+    let bytes = get_bytes_from_file(&options.input_path);
+    let digest = get_digest_with(&options.hasher, &bytes);
+    println!("Hex:    {}", digest);
+    println!("HEX:    {:X}", digest);
+    println!("Binary: {}", digest.to_binary());
+    println!("Base64: {}", digest.to_base64());
+    println!("Raw:    {:?}", digest.as_bytes());
 }
 
 fn main() {
-    part1();
-    part2();
+    let options = options_from_args();
+    part1(&options);
+    part2(&options);
+    if options.custom_hash {
+        custom_hash(&options);
+    }
+    if options.show_encodings {
+        show_encodings(&options);
+    }
+    if let Some(hash_count) = options.benchmark_hashes {
+        benchmark::run(hash_count);
+    }
 }
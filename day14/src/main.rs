@@ -1,51 +1,13 @@
-#![feature(slice_rotate)]
-#![feature(inclusive_range_syntax)]
+extern crate day10;
 
-const INPUT: &'static str = "ugkiagan";
-
-fn reverse_section(slice: &mut [u8], start: usize, end: usize) {
-    if start < end {
-        slice[start..end + 1].reverse();
-    } else if start > end {
-        let reverse_len = (slice.len() - start) + end;
-        slice.rotate(start);
-        slice[0..reverse_len + 1].reverse();
-        slice.rotate(reverse_len - end);
-    }
-}
+use day10::get_digest;
 
-fn apply_hashing_rounds(slice: &mut [u8], lengths: &[u8], rounds: usize) {
-    let mut skip_size = 0;
-    let mut start: usize = 0;
-    let knot_length = slice.len();
-
-    for _ in 0..rounds {
-        for &length in lengths {
-            if length != 0 {
-                let end = (start + length as usize - 1) % knot_length;
-                reverse_section(slice, start, end);
-            }
-            start = (start + length as usize + skip_size) % knot_length;
-            skip_size += 1;
-        }
-    }
-}
-
-fn get_hash(bytes: &[u8]) -> String {
-    let mut twisted_knot: Vec<_> = (0..=255).collect();
-    let mut padded_lengths = bytes.to_vec();
-    padded_lengths.extend_from_slice(&[17, 31, 73, 47, 23]);
-
-    apply_hashing_rounds(&mut twisted_knot, &padded_lengths, 64);
-    twisted_knot.chunks(16)
-        .map(|chunk| chunk.iter().fold(0, |acc, curr| acc ^ curr))
-        .fold(String::new(), |acc, curr| acc + &format!("{:8b}", curr))
-}
+const INPUT: &'static str = "ugkiagan";
 
 fn get_defrag_grid(input: &str) -> Vec<Vec<bool>> {
     let row_input = |row| format!("{}-{}", input, row);
     (0..128).map(
-        |row| get_hash(row_input(row).as_bytes()).chars().map(|c| c == '1').collect()
+        |row| get_digest(row_input(row).as_bytes()).to_binary().chars().map(|c| c == '1').collect()
     ).collect()
 }
 
@@ -1,4 +1,4 @@
-#![feature(slice_rotate)]
+extern crate cycles;
 
 use std::fs::File;
 use std::io::{BufReader, BufRead};
@@ -50,7 +50,7 @@ fn apply_move(programs: &mut Vec<char>, mov: Move) {
     match mov {
         Move::Spin(size) => {
             let len = programs.len();
-            programs.rotate(len - size);
+            programs.rotate_left(len - size);
         },
         Move::Exchange(ix, jx) => programs.swap(ix, jx),
         Move::Partner(pa, pb) => {
@@ -62,22 +62,16 @@ fn apply_move(programs: &mut Vec<char>, mov: Move) {
 }
 
 fn repeat_dance(programs: &[char], dance: &[Move], iterations: usize) -> Vec<char> {
-    let mut seen = Vec::new();
-    let mut current = programs.to_vec();
-
-    for it in 0..iterations {
-        match get_position(&current, &seen) {
-            Some(ix) => return seen[ix + (iterations % (it - ix))].clone(),
-            None => {
-                seen.push(current.clone());
-                for &mov in dance {
-                    apply_move(&mut current, mov);
-                }
-            }
+    let perform_dance = |current: &Vec<char>| {
+        let mut next = current.clone();
+        for &mov in dance {
+            apply_move(&mut next, mov);
         }
-    }
+        next
+    };
 
-    current
+    let cycle = cycles::brent(&programs.to_vec(), &perform_dance);
+    cycle.state_at(&programs.to_vec(), &perform_dance, iterations)
 }
 
 fn part1() {
@@ -0,0 +1,109 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Chars,
+    Graphemes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Normalisation {
+    None,
+    Nfc,
+    Nfkc,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Canonicaliser {
+    unit: Unit,
+    fold_case: bool,
+    normalisation: Normalisation,
+}
+
+impl Canonicaliser {
+    pub fn new() -> Canonicaliser {
+        Canonicaliser {
+            unit: Unit::Chars,
+            fold_case: false,
+            normalisation: Normalisation::None,
+        }
+    }
+
+    pub fn unit(self, unit: Unit) -> Canonicaliser {
+        Canonicaliser { unit, ..self }
+    }
+
+    pub fn fold_case(self, fold_case: bool) -> Canonicaliser {
+        Canonicaliser { fold_case, ..self }
+    }
+
+    pub fn normalisation(self, normalisation: Normalisation) -> Canonicaliser {
+        Canonicaliser { normalisation, ..self }
+    }
+
+    fn apply_normalisation(&self, word: &str) -> String {
+        match self.normalisation {
+            Normalisation::None => word.to_string(),
+            Normalisation::Nfc => word.nfc().collect(),
+            Normalisation::Nfkc => word.nfkc().collect(),
+        }
+    }
+
+    // Normalisation can produce uppercase output under NFKC, and case folding
+    // can produce unnormalised output, so normalise, fold and normalise again
+    // as NFKC_Casefold does.
+    pub fn normalise(&self, word: &str) -> String {
+        if !self.fold_case {
+            return self.apply_normalisation(word);
+        }
+        let folded = self.apply_normalisation(word).to_lowercase();
+        self.apply_normalisation(&folded)
+    }
+
+    pub fn units<'a>(&self, word: &'a str) -> Vec<&'a str> {
+        match self.unit {
+            Unit::Chars => word.char_indices().map(|(idx, c)| &word[idx..idx + c.len_utf8()]).collect(),
+            Unit::Graphemes => word.graphemes(true).collect(),
+        }
+    }
+
+    pub fn sorted(&self, word: &str) -> String {
+        let normalised = self.normalise(word);
+        let mut units = self.units(&normalised);
+        units.sort();
+        units.concat()
+    }
+}
+
+impl Default for Canonicaliser {
+    fn default() -> Canonicaliser {
+        Canonicaliser::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_case_produced_by_nfkc() {
+        let canonicaliser = Canonicaliser::new().fold_case(true).normalisation(Normalisation::Nfkc);
+        assert_eq!(canonicaliser.normalise("\u{210c}"), "h");
+        assert_eq!(canonicaliser.sorted("\u{210c}i"), canonicaliser.sorted("hi"));
+    }
+
+    #[test]
+    fn sorts_multibyte_anagrams() {
+        let canonicaliser = Canonicaliser::new();
+        assert_eq!(canonicaliser.sorted("ébène"), canonicaliser.sorted("nèbée"));
+        assert_ne!(canonicaliser.sorted("ébène"), canonicaliser.sorted("ebene"));
+    }
+
+    #[test]
+    fn keeps_combining_marks_with_their_graphemes() {
+        let canonicaliser = Canonicaliser::new().unit(Unit::Graphemes);
+        assert_eq!(canonicaliser.sorted("e\u{301}t"), canonicaliser.sorted("te\u{301}"));
+        assert_ne!(canonicaliser.sorted("e\u{301}t"), canonicaliser.sorted("\u{301}et"));
+    }
+}
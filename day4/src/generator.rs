@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use policy::{Equivalence, Policy, Rule};
+
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+pub struct Passphrase {
+    pub words: Vec<String>,
+    pub entropy_bits: f64,
+}
+
+pub struct Generator {
+    policy: Policy,
+    words: Vec<String>,
+}
+
+impl Generator {
+    pub fn new(policy: &Policy, wordlist: &[String]) -> Generator {
+        let mut policy = policy.clone();
+        if !policy.rules().contains(&Rule::NoDuplicates(Equivalence::Anagram)) {
+            policy = policy.with_rule(Rule::NoDuplicates(Equivalence::Anagram));
+        }
+
+        let mut seen_words = HashSet::new();
+        let words = wordlist.iter()
+            .flat_map(|line| line.split_whitespace())
+            .filter(|&word| policy.rules().iter().all(|rule| match *rule {
+                Rule::MinWordLength(_) | Rule::BannedWords(_) => policy.satisfies(rule, &[word]),
+                _ => true,
+            }))
+            .filter(|&word| seen_words.insert(policy.key(word, Equivalence::Exact)))
+            .map(|word| word.to_string())
+            .collect();
+
+        Generator { policy, words }
+    }
+
+    pub fn from_file(policy: &Policy, path: &str) -> Generator {
+        let file = File::open(path).expect("Unable to open file");
+        let reader = BufReader::new(file);
+        let wordlist: Vec<String> = reader.lines().map(|line| line.unwrap()).collect();
+        Generator::new(policy, &wordlist)
+    }
+
+    fn is_compatible(&self, word: &str, chosen: &[String]) -> bool {
+        self.policy.rules().iter().all(|rule| match *rule {
+            Rule::NoDuplicates(equivalence) => chosen.iter().all(|other| !self.policy.are_equivalent(word, other, equivalence)),
+            _ => true,
+        })
+    }
+
+    // Each word is drawn uniformly from those compatible with the words already
+    // chosen, so the entropy of the whole passphrase is the sum of log2 of the
+    // number of candidates available at each draw.
+    pub fn generate(&self, word_count: usize, seed: u64) -> Result<Passphrase, String> {
+        for rule in self.policy.rules() {
+            match *rule {
+                Rule::MinWords(count) if word_count < count => return Err(format!("The policy requires at least {} words", count)),
+                Rule::MaxWords(count) if word_count > count => return Err(format!("The policy allows at most {} words", count)),
+                _ => {},
+            }
+        }
+
+        let mut rng = SplitMix64::new(seed);
+        let mut words: Vec<String> = Vec::with_capacity(word_count);
+        let mut entropy_bits = 0.0;
+
+        for _ in 0..word_count {
+            let candidates: Vec<&String> = self.words.iter()
+                .filter(|word| self.is_compatible(word, &words))
+                .collect();
+            if candidates.is_empty() {
+                return Err(format!("Wordlist is exhausted after {} of {} words", words.len(), word_count));
+            }
+
+            entropy_bits += (candidates.len() as f64).log2();
+            let choice = rng.next_below(candidates.len() as u64) as usize;
+            words.push(candidates[choice].clone());
+        }
+
+        if !self.policy.is_valid(&words.join(" ")) {
+            return Err(format!("No passphrase of {} words satisfies the policy", word_count));
+        }
+
+        Ok(Passphrase { words, entropy_bits })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use canonical::Canonicaliser;
+    use super::*;
+
+    fn wordlist(words: &str) -> Vec<String> {
+        words.lines().map(|line| line.to_string()).collect()
+    }
+
+    fn sorted(word: &str) -> Vec<char> {
+        let mut chars: Vec<char> = word.chars().collect();
+        chars.sort();
+        chars
+    }
+
+    #[test]
+    fn gives_the_same_passphrase_for_the_same_seed() {
+        let generator = Generator::new(&Policy::no_anagrams(Canonicaliser::new()), &wordlist("apple banana\ncherry damson\nelder fig grape"));
+        let first = generator.generate(4, 42).unwrap();
+        let second = generator.generate(4, 42).unwrap();
+        assert_eq!(first.words, second.words);
+        assert_eq!(first.entropy_bits, second.entropy_bits);
+        let others: Vec<_> = (0..20).map(|seed| generator.generate(4, seed).unwrap().words).collect();
+        assert!(others.iter().any(|words| *words != first.words));
+    }
+
+    #[test]
+    fn never_breaks_the_anagram_rule() {
+        let words = wordlist("listen silent enlist tinsel\nstone notes onset tones\nevil vile live veil\nrat tar art\ncat dog");
+        let anagrams = Policy::no_anagrams(Canonicaliser::new());
+        for policy in &[Policy::no_anagrams(Canonicaliser::new()), Policy::no_repeats(Canonicaliser::new()), Policy::parse("").unwrap()] {
+            let generator = Generator::new(policy, &words);
+            for seed in 0..200 {
+                let passphrase = generator.generate(5, seed).unwrap().words.join(" ");
+                assert!(policy.is_valid(&passphrase), "{} breaks its own policy", passphrase);
+                assert!(anagrams.is_valid(&passphrase), "{} contains anagrams", passphrase);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_to_the_word_count_limits() {
+        let generator = Generator::new(&Policy::parse("min-words 3\nmax-words 4").unwrap(), &wordlist("a b c d e f"));
+        assert_eq!(generator.generate(2, 0).err(), Some("The policy requires at least 3 words".to_string()));
+        assert_eq!(generator.generate(5, 0).err(), Some("The policy allows at most 4 words".to_string()));
+        assert_eq!(generator.generate(3, 0).map(|passphrase| passphrase.words.len()), Ok(3));
+    }
+
+    #[test]
+    fn reports_an_exhausted_wordlist() {
+        let generator = Generator::new(&Policy::no_repeats(Canonicaliser::new()), &wordlist("abc bca\ncab"));
+        assert_eq!(generator.generate(2, 0).err(), Some("Wordlist is exhausted after 1 of 2 words".to_string()));
+    }
+
+    #[test]
+    fn adds_up_the_entropy_of_each_draw() {
+        let words = wordlist("abc bca cab\nxyz\npq");
+        let generator = Generator::new(&Policy::parse("").unwrap(), &words);
+        for seed in 0..50 {
+            let passphrase = generator.generate(3, seed).unwrap();
+            let mut expected = 0.0;
+            for idx in 0..passphrase.words.len() {
+                let chosen: Vec<Vec<char>> = passphrase.words[..idx].iter().map(|word| sorted(word)).collect();
+                let candidates = ["abc", "bca", "cab", "xyz", "pq"].iter().filter(|word| !chosen.contains(&sorted(word))).count();
+                expected += (candidates as f64).log2();
+            }
+            assert!((passphrase.entropy_bits - expected).abs() < 1e-9, "{:?} has {} bits, not {}", passphrase.words, passphrase.entropy_bits, expected);
+        }
+    }
+}
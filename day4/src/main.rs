@@ -1,6 +1,16 @@
+extern crate unicode_normalization;
+extern crate unicode_segmentation;
+
+mod canonical;
+mod generator;
+mod policy;
+
+use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::collections::HashSet;
+use canonical::{Canonicaliser, Normalisation, Unit};
+use generator::Generator;
+use policy::Policy;
 
 const INPUT_PATH: &'static str = "inputs/input.txt";
 
@@ -10,46 +20,91 @@ fn read_words_from_file(path: &str) -> Vec<String> {
     reader.lines().map(|line| line.unwrap()).collect()
 }
 
-fn contains_no_repeats_with_map<F: Fn(&str) -> String>(passphrase: &str, mapper: F) -> bool {
-    let mut seen_words: HashSet<String> = HashSet::new();
-    for word in passphrase.split_whitespace().map(|word| mapper(word)) {
-        if seen_words.contains(&word) {
-            return false;
-        }
-        seen_words.insert(word);
-    }
-    true
-}
-
-fn sort_chars_of_string(word: &str) -> String {
-    let mut chars: Vec<u8> = word.bytes().collect();
-    chars.sort();
-    String::from_utf8(chars).unwrap()
+struct Options {
+    canonicaliser: Canonicaliser,
+    policy_path: Option<String>,
+    wordlist_path: Option<String>,
+    word_count: Option<usize>,
+    seed: Option<u64>,
 }
 
-fn is_valid(passphrase: &str) -> bool {
-    contains_no_repeats_with_map(passphrase, |word| word.to_string())
+fn options_from_args() -> Options {
+    let mut options = Options {
+        canonicaliser: Canonicaliser::new(),
+        policy_path: None,
+        wordlist_path: None,
+        word_count: None,
+        seed: None,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--graphemes" => options.canonicaliser = options.canonicaliser.unit(Unit::Graphemes),
+            "--fold-case" => options.canonicaliser = options.canonicaliser.fold_case(true),
+            "--nfc" => options.canonicaliser = options.canonicaliser.normalisation(Normalisation::Nfc),
+            "--nfkc" => options.canonicaliser = options.canonicaliser.normalisation(Normalisation::Nfkc),
+            "--policy" => options.policy_path = Some(args.next().expect("--policy expects a path")),
+            "--generate" => options.wordlist_path = Some(args.next().expect("--generate expects a path")),
+            "--words" => options.word_count = args.next().and_then(|n| n.parse().ok()),
+            "--seed" => options.seed = args.next().and_then(|n| n.parse().ok()),
+            _ => panic!("Unrecognised argument {}", arg),
+        }
+    }
+    options
 }
 
-fn is_valid_with_anagrams(passphrase: &str) -> bool {
-    contains_no_repeats_with_map(passphrase, |word| sort_chars_of_string(word))
+fn count_valid(passphrases: &[String], policy: &Policy) -> usize {
+    passphrases.iter().filter(|pass| policy.is_valid(pass)).count()
 }
 
 fn part1() {
     let passphrases = read_words_from_file(INPUT_PATH);
-    let answer = passphrases.iter().filter(|pass| is_valid(pass)).count();
+    let answer = count_valid(&passphrases, &Policy::no_repeats(Canonicaliser::new()));
 
     println!("The answer to Part 1 is {}", answer);
 }
 
-fn part2() {
+fn part2(options: &Options) {
     let passphrases = read_words_from_file(INPUT_PATH);
-    let answer = passphrases.iter().filter(|pass| is_valid_with_anagrams(pass)).count();
+    let answer = count_valid(&passphrases, &Policy::no_anagrams(options.canonicaliser));
 
     println!("The answer to Part 2 is {}", answer);
 }
 
+fn load_policy(path: &str) -> Policy {
+    Policy::from_file(path).unwrap_or_else(|e| panic!("Invalid policy: {}", e))
+}
+
+fn custom_policy(path: &str) {
+    let passphrases = read_words_from_file(INPUT_PATH);
+    let answer = count_valid(&passphrases, &load_policy(path));
+
+    println!("Passphrases valid under {}: {}", path, answer);
+}
+
+fn generate(options: &Options, wordlist_path: &str) {
+    let policy = match options.policy_path {
+        Some(ref path) => load_policy(path),
+        None => Policy::no_anagrams(options.canonicaliser),
+    };
+    let word_count = options.word_count.expect("--generate requires a numeric --words");
+    let seed = options.seed.expect("--generate requires a numeric --seed");
+
+    let generator = Generator::from_file(&policy, wordlist_path);
+    match generator.generate(word_count, seed) {
+        Ok(passphrase) => println!("Generated {} ({:.1} bits of entropy)", passphrase.words.join(" "), passphrase.entropy_bits),
+        Err(e) => println!("Unable to generate a passphrase: {}", e),
+    }
+}
+
 fn main() {
+    let options = options_from_args();
     part1();
-    part2();
+    part2(&options);
+    if let Some(ref path) = options.policy_path {
+        custom_policy(path);
+    }
+    if let Some(ref path) = options.wordlist_path {
+        generate(&options, path);
+    }
 }
\ No newline at end of file
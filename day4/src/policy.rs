@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+
+use canonical::{Canonicaliser, Normalisation, Unit};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Equivalence {
+    Exact,
+    CaseInsensitive,
+    Anagram,
+    EditDistanceOne,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    NoDuplicates(Equivalence),
+    MinWords(usize),
+    MaxWords(usize),
+    MinWordLength(usize),
+    BannedWords(HashSet<String>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Policy {
+    canonicaliser: Canonicaliser,
+    rules: Vec<Rule>,
+}
+
+fn within_one_edit(first: &[&str], second: &[&str]) -> bool {
+    let (shorter, longer) = if first.len() <= second.len() { (first, second) } else { (second, first) };
+    if longer.len() - shorter.len() > 1 {
+        return false;
+    }
+
+    let prefix = shorter.iter().zip(longer.iter()).take_while(|&(a, b)| a == b).count();
+    if shorter.len() == longer.len() {
+        shorter[prefix..].iter().skip(1).eq(longer[prefix..].iter().skip(1))
+    } else {
+        shorter[prefix..] == longer[prefix + 1..]
+    }
+}
+
+fn parse_number(value: Option<&str>, directive: &str) -> Result<usize, String> {
+    value.and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("{} expects a number", directive))
+}
+
+impl Policy {
+    pub fn new(canonicaliser: Canonicaliser) -> Policy {
+        Policy { canonicaliser, rules: Vec::new() }
+    }
+
+    pub fn with_rule(mut self, rule: Rule) -> Policy {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn no_repeats(canonicaliser: Canonicaliser) -> Policy {
+        Policy::new(canonicaliser).with_rule(Rule::NoDuplicates(Equivalence::Exact))
+    }
+
+    pub fn no_anagrams(canonicaliser: Canonicaliser) -> Policy {
+        Policy::new(canonicaliser).with_rule(Rule::NoDuplicates(Equivalence::Anagram))
+    }
+
+    pub fn from_file(path: &str) -> Result<Policy, String> {
+        let mut file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path, e))?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).map_err(|e| format!("Unable to read {}: {}", path, e))?;
+        Policy::parse(&contents)
+    }
+
+    // One directive per line; blank lines and lines starting with '#' are ignored.
+    //
+    //     unit chars|graphemes
+    //     normalise none|nfc|nfkc
+    //     fold-case
+    //     no-duplicates exact|case-insensitive|anagram|edit-distance-1
+    //     min-words N
+    //     max-words N
+    //     min-word-length N
+    //     banned WORD...
+    pub fn parse(config: &str) -> Result<Policy, String> {
+        let mut canonicaliser = Canonicaliser::new();
+        let mut rules = Vec::new();
+
+        for (line_number, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let directive = parts.next().unwrap();
+            let value = parts.next();
+            let in_context = |e: String| format!("Line {}: {}", line_number + 1, e);
+
+            match directive {
+                "unit" => canonicaliser = canonicaliser.unit(match value {
+                    Some("chars") => Unit::Chars,
+                    Some("graphemes") => Unit::Graphemes,
+                    _ => return Err(in_context("unit expects chars or graphemes".to_string())),
+                }),
+                "normalise" => canonicaliser = canonicaliser.normalisation(match value {
+                    Some("none") => Normalisation::None,
+                    Some("nfc") => Normalisation::Nfc,
+                    Some("nfkc") => Normalisation::Nfkc,
+                    _ => return Err(in_context("normalise expects none, nfc or nfkc".to_string())),
+                }),
+                "fold-case" => canonicaliser = canonicaliser.fold_case(true),
+                "no-duplicates" => rules.push(Rule::NoDuplicates(match value {
+                    Some("exact") => Equivalence::Exact,
+                    Some("case-insensitive") => Equivalence::CaseInsensitive,
+                    Some("anagram") => Equivalence::Anagram,
+                    Some("edit-distance-1") => Equivalence::EditDistanceOne,
+                    _ => return Err(in_context(
+                        "no-duplicates expects exact, case-insensitive, anagram or edit-distance-1".to_string()
+                    )),
+                })),
+                "min-words" => rules.push(Rule::MinWords(parse_number(value, directive).map_err(in_context)?)),
+                "max-words" => rules.push(Rule::MaxWords(parse_number(value, directive).map_err(in_context)?)),
+                "min-word-length" => rules.push(Rule::MinWordLength(parse_number(value, directive).map_err(in_context)?)),
+                "banned" => rules.push(Rule::BannedWords(value.into_iter().chain(parts).map(|w| w.to_string()).collect())),
+                _ => return Err(in_context(format!("Unknown directive {}", directive))),
+            }
+        }
+
+        let canonicaliser_ref = &canonicaliser;
+        let rules = rules.into_iter().map(|rule| match rule {
+            Rule::BannedWords(words) => Rule::BannedWords(words.iter().map(|w| canonicaliser_ref.normalise(w)).collect()),
+            rule => rule,
+        }).collect();
+
+        Ok(Policy { canonicaliser, rules })
+    }
+
+    pub fn key(&self, word: &str, equivalence: Equivalence) -> String {
+        match equivalence {
+            Equivalence::Exact | Equivalence::EditDistanceOne => self.canonicaliser.normalise(word),
+            Equivalence::CaseInsensitive => self.canonicaliser.fold_case(true).normalise(word),
+            Equivalence::Anagram => self.canonicaliser.sorted(word),
+        }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn are_equivalent(&self, first: &str, second: &str, equivalence: Equivalence) -> bool {
+        let (first, second) = (self.key(first, equivalence), self.key(second, equivalence));
+        match equivalence {
+            Equivalence::EditDistanceOne => within_one_edit(&self.canonicaliser.units(&first), &self.canonicaliser.units(&second)),
+            _ => first == second,
+        }
+    }
+
+    fn contains_no_repeats(&self, words: &[&str], equivalence: Equivalence) -> bool {
+        match equivalence {
+            Equivalence::EditDistanceOne => {
+                let keys: Vec<String> = words.iter().map(|word| self.key(word, equivalence)).collect();
+                let units: Vec<Vec<&str>> = keys.iter().map(|key| self.canonicaliser.units(key)).collect();
+                (0..units.len()).all(|i| (0..i).all(|j| !within_one_edit(&units[i], &units[j])))
+            },
+            _ => {
+                let mut seen_words: HashSet<String> = HashSet::new();
+                words.iter().all(|word| seen_words.insert(self.key(word, equivalence)))
+            },
+        }
+    }
+
+    pub fn satisfies(&self, rule: &Rule, words: &[&str]) -> bool {
+        match *rule {
+            Rule::NoDuplicates(equivalence) => self.contains_no_repeats(words, equivalence),
+            Rule::MinWords(count) => words.len() >= count,
+            Rule::MaxWords(count) => words.len() <= count,
+            Rule::MinWordLength(length) => words.iter().all(|word| self.canonicaliser.units(&self.canonicaliser.normalise(word)).len() >= length),
+            Rule::BannedWords(ref banned) => words.iter().all(|word| !banned.contains(&self.canonicaliser.normalise(word))),
+        }
+    }
+
+    pub fn is_valid(&self, passphrase: &str) -> bool {
+        let words: Vec<&str> = passphrase.split_whitespace().collect();
+        self.rules.iter().all(|rule| self.satisfies(rule, &words))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_directive() {
+        let config = "# A strict policy\n\
+                      unit graphemes\n\
+                      normalise nfc\n\
+                      fold-case\n\
+                      \n\
+                      no-duplicates anagram\n\
+                      min-words 3\n\
+                      max-words 5\n\
+                      min-word-length 2\n\
+                      banned Password letmein\n";
+        let policy = Policy::parse(config).unwrap();
+        let canonicaliser = Canonicaliser::new().unit(Unit::Graphemes).normalisation(Normalisation::Nfc).fold_case(true);
+        let banned = ["password", "letmein"].iter().map(|w| w.to_string()).collect();
+        assert_eq!(policy, Policy {
+            canonicaliser,
+            rules: vec![
+                Rule::NoDuplicates(Equivalence::Anagram),
+                Rule::MinWords(3),
+                Rule::MaxWords(5),
+                Rule::MinWordLength(2),
+                Rule::BannedWords(banned),
+            ],
+        });
+    }
+
+    #[test]
+    fn reports_bad_lines_with_their_numbers() {
+        assert_eq!(Policy::parse("fold-case\nunit bytes"), Err("Line 2: unit expects chars or graphemes".to_string()));
+        assert_eq!(Policy::parse("min-words many"), Err("Line 1: min-words expects a number".to_string()));
+        assert_eq!(Policy::parse("\n\nallow-all"), Err("Line 3: Unknown directive allow-all".to_string()));
+    }
+
+    #[test]
+    fn applies_parsed_rules() {
+        let policy = Policy::parse("no-duplicates edit-distance-1\nbanned cat").unwrap();
+        assert!(policy.is_valid("dog bird fish"));
+        assert!(!policy.is_valid("dog dig"));
+        assert!(!policy.is_valid("dog cat"));
+        assert!(!policy.is_valid("dog dogs"));
+    }
+}
@@ -0,0 +1,201 @@
+// Under the part 2 rule every offset drifts towards the range [2, 3], and once
+// there it flips between 2 and 3 on each visit without ever leaving. Much of
+// the maze therefore settles into blocks of 2s and 3s which can only be
+// crossed forwards, and a walk leaving one block enters the next at offset 0,
+// 1 or 2. Settled blocks are stored as bitmasks (bit set means 3) so that a
+// whole run of them can be crossed with one table lookup per block.
+
+const BLOCK_SIZE: usize = 16;
+const ENTRY_OFFSETS: usize = 3;
+
+struct Crossing {
+    new_state: u16,
+    steps: u16,
+}
+
+struct BlockTables {
+    // For each block state, the exit offset into the next block for each entry
+    // offset, packed two bits per entry offset so that the walk from one block
+    // to the next needs no memory access on its critical path.
+    exits: Vec<u8>,
+    crossings: Vec<Crossing>,
+}
+
+fn build_tables() -> BlockTables {
+    let mut exits = Vec::with_capacity(1 << BLOCK_SIZE);
+    let mut crossings = Vec::with_capacity((1 << BLOCK_SIZE) * ENTRY_OFFSETS);
+    for initial_state in 0..1usize << BLOCK_SIZE {
+        let mut packed_exits = 0;
+        for entry_offset in 0..ENTRY_OFFSETS {
+            let (mut state, mut offset, mut steps) = (initial_state, entry_offset, 0);
+            while offset < BLOCK_SIZE {
+                let jump = 2 + ((state >> offset) & 1);
+                state ^= 1 << offset;
+                offset += jump;
+                steps += 1;
+            }
+            packed_exits |= ((offset - BLOCK_SIZE) as u8) << (2 * entry_offset);
+            crossings.push(Crossing { new_state: state as u16, steps });
+        }
+        exits.push(packed_exits);
+    }
+    BlockTables { exits, crossings }
+}
+
+fn is_settled(offset: i64) -> bool {
+    offset == 2 || offset == 3
+}
+
+pub struct AcceleratedSolver {
+    tables: BlockTables,
+}
+
+impl AcceleratedSolver {
+    pub fn new() -> AcceleratedSolver {
+        AcceleratedSolver { tables: build_tables() }
+    }
+
+    fn pack_block(maze: &[i64], block: usize) -> u16 {
+        let cells = &maze[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE];
+        cells.iter().enumerate().fold(0, |acc, (idx, &offset)| acc | (((offset - 2) as u16) << idx))
+    }
+
+    fn unpack_block(maze: &mut [i64], block: usize, state: u16) {
+        for (idx, cell) in maze[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE].iter_mut().enumerate() {
+            *cell = 2 + ((state >> idx) & 1) as i64;
+        }
+    }
+
+    // Crosses the run of settled blocks starting at `first_block`, entering
+    // the first at `entry_offset` (which must be 0, 1 or 2), and returns the
+    // index reached just past the run along with the steps taken.
+    fn cross_settled_blocks(&self, settled_blocks: &mut [Option<u16>], first_block: usize, entry_offset: usize) -> (usize, u64) {
+        let (mut block, mut entry_offset, mut steps) = (first_block, entry_offset, 0);
+        while let Some(&mut Some(ref mut state)) = settled_blocks.get_mut(block) {
+            let crossing = &self.tables.crossings[*state as usize * ENTRY_OFFSETS + entry_offset];
+            let exits = self.tables.exits[*state as usize];
+            *state = crossing.new_state;
+            steps += crossing.steps as u64;
+            entry_offset = ((exits >> (2 * entry_offset)) & 3) as usize;
+            block += 1;
+        }
+        (block * BLOCK_SIZE + entry_offset, steps)
+    }
+
+    // Gives the same result as `steps_to_exit_with_rule` with the part 2 rule,
+    // including the final state of the maze. Any full block can settle, not
+    // just those at the front, so mazes whose backward jumps are short gain
+    // too.
+    pub fn steps_to_exit(&self, maze: &mut [i64]) -> u64 {
+        let full_blocks = maze.len() / BLOCK_SIZE;
+        let mut settled_blocks: Vec<Option<u16>> = vec![None; full_blocks];
+        let mut steps = 0;
+        let mut current_idx: i64 = 0;
+
+        while current_idx >= 0 && current_idx < maze.len() as i64 {
+            let idx = current_idx as usize;
+            let block = idx / BLOCK_SIZE;
+
+            if let Some(&Some(mut state)) = settled_blocks.get(block) {
+                // Walk within the block we landed in until we cross into the
+                // next one, after which every entry offset is small.
+                let mut offset = idx % BLOCK_SIZE;
+                while offset < BLOCK_SIZE {
+                    let jump = 2 + ((state >> offset) & 1) as usize;
+                    state ^= 1 << offset;
+                    offset += jump;
+                    steps += 1;
+                }
+                settled_blocks[block] = Some(state);
+
+                let (next_idx, crossing_steps) = self.cross_settled_blocks(&mut settled_blocks, block + 1, offset - BLOCK_SIZE);
+                current_idx = next_idx as i64;
+                steps += crossing_steps;
+                continue;
+            }
+
+            current_idx += maze[idx];
+            maze[idx] = if maze[idx] >= 3 { maze[idx] - 1 } else { maze[idx] + 1 };
+            steps += 1;
+
+            if block < full_blocks && is_settled(maze[idx])
+                && maze[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE].iter().all(|&o| is_settled(o)) {
+                settled_blocks[block] = Some(AcceleratedSolver::pack_block(maze, block));
+            }
+        }
+
+        for (block, state) in settled_blocks.into_iter().enumerate() {
+            if let Some(state) = state {
+                AcceleratedSolver::unpack_block(maze, block, state);
+            }
+        }
+
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {steps_to_exit_with_rule, Outcome, RunOptions};
+
+    struct SplitMix64(u64);
+
+    impl SplitMix64 {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            (z ^ (z >> 31)) % n
+        }
+    }
+
+    fn check(solver: &AcceleratedSolver, maze: &[i64]) {
+        let (mut expected_maze, mut actual_maze) = (maze.to_vec(), maze.to_vec());
+        let rule = |offset| Ok(if offset >= 3 { offset - 1 } else { offset + 1 });
+        let expected = steps_to_exit_with_rule(&mut expected_maze, rule, &RunOptions::default(), None);
+        let actual = solver.steps_to_exit(&mut actual_maze);
+        assert_eq!(Outcome::Exited(actual), expected, "Different step counts for {:?}", maze);
+        assert_eq!(actual_maze, expected_maze, "Different final mazes for {:?}", maze);
+    }
+
+    #[test]
+    fn matches_the_example() {
+        let mut maze = vec![0, 3, 0, 1, -3];
+        assert_eq!(AcceleratedSolver::new().steps_to_exit(&mut maze), 10);
+        assert_eq!(maze, vec![2, 3, 2, 3, -1]);
+    }
+
+    // Lengths which are not a multiple of the block size leave a partial
+    // block at the end, which never settles.
+    #[test]
+    fn matches_the_step_by_step_solver() {
+        let solver = AcceleratedSolver::new();
+        let mut rng = SplitMix64(5);
+        for _ in 0..3000 {
+            let len = rng.below(8 * BLOCK_SIZE as u64) as usize + 1;
+            let maze: Vec<i64> = (0..len).map(|idx| match rng.below(10) {
+                0 => rng.below(5) as i64,
+                1..=4 => 2 + rng.below(2) as i64,
+                _ => -(rng.below(idx as u64 + 1) as i64),
+            }).collect();
+            check(&solver, &maze);
+        }
+    }
+
+    #[test]
+    fn matches_the_step_by_step_solver_on_settled_mazes() {
+        let solver = AcceleratedSolver::new();
+        let mut rng = SplitMix64(11);
+        for _ in 0..200 {
+            let len = rng.below(40 * BLOCK_SIZE as u64) as usize + 1;
+            let mut maze: Vec<i64> = (0..len).map(|_| 2 + rng.below(2) as i64).collect();
+            for _ in 0..rng.below(4) {
+                let idx = rng.below(len as u64) as usize;
+                maze[idx] = -(rng.below(idx as u64 + 1) as i64);
+            }
+            check(&solver, &maze);
+        }
+    }
+}
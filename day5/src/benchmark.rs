@@ -0,0 +1,37 @@
+use std::time::{Duration, Instant};
+
+use accelerated::AcceleratedSolver;
+use {steps_to_exit_with_rule, Outcome, RunOptions};
+
+fn time<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+}
+
+// Times both part 2 solvers on the maze repeated `scale` times over, and
+// checks that they agree on the steps taken and the final maze.
+pub fn run(maze: &[i64], scale: usize) {
+    let maze: Vec<i64> = (0..scale).flat_map(|_| maze.iter().cloned()).collect();
+    println!("Solving part 2 for a maze of {} cells", maze.len());
+
+    let mut stepped_maze = maze.clone();
+    let rule = |offset| Ok(if offset >= 3 { offset - 1 } else { offset + 1 });
+    let (stepped, stepped_time) = time(|| steps_to_exit_with_rule(&mut stepped_maze, rule, &RunOptions::default(), None));
+    println!("Step by step: {:?} in {:.1}ms", stepped, millis(stepped_time));
+
+    let (solver, table_time) = time(AcceleratedSolver::new);
+    let mut accelerated_maze = maze.clone();
+    let (accelerated, accelerated_time) = time(|| solver.steps_to_exit(&mut accelerated_maze));
+    println!("Accelerated: {} steps in {:.1}ms after {:.1}ms building tables ({:.1}x faster, {:.1}x including the tables)",
+             accelerated, millis(accelerated_time), millis(table_time),
+             millis(stepped_time) / millis(accelerated_time),
+             millis(stepped_time) / (millis(table_time) + millis(accelerated_time)));
+
+    assert_eq!(Outcome::Exited(accelerated), stepped, "The accelerated solver disagrees on the step count");
+    assert!(accelerated_maze == stepped_maze, "The accelerated solver leaves a different maze");
+}
@@ -0,0 +1,191 @@
+use rule::EvaluationError;
+use trace::Trace;
+use {Outcome, RunOptions};
+
+const INDEX_WEIGHT: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// A fixed pseudo-random weight for each cell, from SplitMix64's finaliser.
+fn weight(idx: usize) -> u64 {
+    let mut z = (idx as u64).wrapping_add(1).wrapping_mul(INDEX_WEIGHT);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) | 1
+}
+
+// The position and maze of a run. The hash is a weighted sum of the index
+// and every cell, so each step updates it in O(1), and two states are only
+// compared cell by cell when their hashes match.
+#[derive(Clone)]
+struct State {
+    idx: i64,
+    maze: Vec<i64>,
+    hash: u64,
+}
+
+impl State {
+    fn new(maze: &[i64]) -> State {
+        let hash = maze.iter().enumerate()
+            .fold(0u64, |acc, (idx, &offset)| acc.wrapping_add(weight(idx).wrapping_mul(offset as u64)));
+        State { idx: 0, maze: maze.to_vec(), hash }
+    }
+
+    fn has_exited(&self) -> bool {
+        self.idx < 0 || self.idx >= self.maze.len() as i64
+    }
+
+    fn step<F: Fn(i64) -> Result<i64, EvaluationError>>(&mut self, rule: &F) -> Result<(), EvaluationError> {
+        let idx = self.idx as usize;
+        let offset = self.maze[idx];
+        let updated = rule(offset)?;
+        self.maze[idx] = updated;
+        self.idx += offset;
+        self.hash = self.hash
+            .wrapping_add(weight(idx).wrapping_mul(updated.wrapping_sub(offset) as u64))
+            .wrapping_add(INDEX_WEIGHT.wrapping_mul(offset as u64));
+        Ok(())
+    }
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &State) -> bool {
+        self.hash == other.hash && self.idx == other.idx && self.maze == other.maze
+    }
+}
+
+// The number of steps before the run first enters its cycle, found by
+// walking from the start with a second run `period` steps ahead. Neither run
+// goes further than the run which found the cycle, so the rule cannot fail.
+fn tail_length<F: Fn(i64) -> Result<i64, EvaluationError>>(start: &State, rule: &F, period: u64) -> u64 {
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    for _ in 0..period {
+        hare.step(rule).expect("The rule failed on a step which had succeeded");
+    }
+    let mut tail = 0;
+    while tortoise != hare {
+        tortoise.step(rule).expect("The rule failed on a step which had succeeded");
+        hare.step(rule).expect("The rule failed on a step which had succeeded");
+        tail += 1;
+    }
+    tail
+}
+
+// Brent's algorithm, run alongside the maze so that it needs a few copies of
+// the maze however long the run goes on for. A cycle is only confirmed some
+// way into its second time round, so a step limit can cut a run off before
+// it reports a cycle which had already begun.
+pub fn steps_to_exit_detecting_cycles<F: Fn(i64) -> Result<i64, EvaluationError>>(maze: &mut [i64], rule: F, options: &RunOptions, mut trace: Option<&mut Trace>) -> Outcome {
+    let start = State::new(maze);
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    let (mut power, mut period) = (1, 0);
+    let mut steps = 0;
+
+    let outcome = loop {
+        if options.step_limit.is_some_and(|limit| steps >= limit) {
+            break Outcome::StepLimitReached(steps);
+        }
+
+        if let Some(ref mut trace) = trace {
+            trace.record(hare.idx as usize);
+        }
+        if let Err(error) = hare.step(&rule) {
+            let offset = hare.maze[hare.idx as usize];
+            break Outcome::RuleFailed { steps, offset, error };
+        }
+        steps += 1;
+        period += 1;
+
+        if hare.has_exited() {
+            break Outcome::Exited(steps);
+        }
+        if hare == tortoise {
+            let tail = tail_length(&start, &rule, period);
+            break Outcome::Cycle { steps: tail + period, cycle_start: tail };
+        }
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+    };
+
+    maze.copy_from_slice(&hare.maze);
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+
+    // Remembers every state, as the detection originally did.
+    fn reference<F: Fn(i64) -> Result<i64, EvaluationError>>(maze: &mut [i64], rule: F) -> Outcome {
+        let mut seen = HashMap::new();
+        let (mut idx, mut steps) = (0i64, 0);
+        loop {
+            if let Some(&cycle_start) = seen.get(&(idx, maze.to_vec())) {
+                return Outcome::Cycle { steps, cycle_start };
+            }
+            seen.insert((idx, maze.to_vec()), steps);
+            let offset = maze[idx as usize];
+            maze[idx as usize] = rule(offset).unwrap();
+            idx += offset;
+            steps += 1;
+            if idx < 0 || idx >= maze.len() as i64 {
+                return Outcome::Exited(steps);
+            }
+        }
+    }
+
+    fn detect<F: Fn(i64) -> Result<i64, EvaluationError>>(maze: &mut [i64], rule: F) -> Outcome {
+        let options = RunOptions { detect_cycles: true, ..RunOptions::default() };
+        steps_to_exit_detecting_cycles(maze, rule, &options, None)
+    }
+
+    #[test]
+    fn finds_the_first_repeated_state() {
+        assert_eq!(detect(&mut [0], Ok), Outcome::Cycle { steps: 1, cycle_start: 0 });
+        assert_eq!(detect(&mut [1, -1], Ok), Outcome::Cycle { steps: 2, cycle_start: 0 });
+        assert_eq!(detect(&mut [0, 3, 0, 1, -3], |offset| Ok(offset + 1)), Outcome::Exited(5));
+    }
+
+    #[test]
+    fn agrees_with_remembering_every_state() {
+        let mut seed = 1u64;
+        for _ in 0..500 {
+            seed = weight(seed as usize);
+            let len = (seed % 6) as usize + 1;
+            let maze: Vec<i64> = (0..len).map(|idx| (weight(seed as usize + idx) % 5) as i64 - 2).collect();
+            let rule = |offset: i64| Ok(if offset > 0 { offset - 2 } else { offset + 1 });
+
+            let (mut expected_maze, mut actual_maze) = (maze.clone(), maze.clone());
+            let expected = reference(&mut expected_maze, rule);
+            let actual = detect(&mut actual_maze, rule);
+            assert_eq!(actual, expected, "Disagreement on {:?}", maze);
+            if let Outcome::Exited(_) = expected {
+                assert_eq!(actual_maze, expected_maze, "Different final mazes for {:?}", maze);
+            }
+        }
+    }
+
+    #[test]
+    fn stops_when_the_rule_fails() {
+        let rule = |offset: i64| if offset == 3 { Err(EvaluationError::DivisionByZero) } else { Ok(offset + 1) };
+        let expected = Outcome::RuleFailed { steps: 2, offset: 3, error: EvaluationError::DivisionByZero };
+        let mut maze = [0, 3, 0, 1, -3];
+        assert_eq!(detect(&mut maze, rule), expected);
+        assert_eq!(maze, [2, 3, 0, 1, -3]);
+
+        let mut maze = [0, 3, 0, 1, -3];
+        assert_eq!(::steps_to_exit_with_rule(&mut maze, rule, &RunOptions::default(), None), expected);
+        assert_eq!(maze, [2, 3, 0, 1, -3]);
+    }
+
+    #[test]
+    fn stops_at_the_step_limit() {
+        let options = RunOptions { detect_cycles: true, step_limit: Some(3), ..RunOptions::default() };
+        let outcome = steps_to_exit_detecting_cycles(&mut [0, 3, 0, 1, -3], |offset| Ok(offset + 1), &options, None);
+        assert_eq!(outcome, Outcome::StepLimitReached(3));
+    }
+}
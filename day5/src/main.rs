@@ -1,5 +1,15 @@
+mod accelerated;
+mod benchmark;
+mod cycle;
+mod rule;
+mod trace;
+
+use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use accelerated::AcceleratedSolver;
+use rule::{EvaluationError, OffsetRule};
+use trace::Trace;
 
 const INPUT_PATH: &'static str = "inputs/input.txt";
 
@@ -12,14 +22,72 @@ fn read_maze_from_file(path: &str) -> Vec<i64> {
         .collect()
 }
 
-fn steps_to_exit_with_rule<F: Fn(i64) -> i64>(maze: &mut [i64], rule: F) -> u64 {
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Exited(u64),
+    StepLimitReached(u64),
+    Cycle { steps: u64, cycle_start: u64 },
+    RuleFailed { steps: u64, offset: i64, error: EvaluationError },
+}
+
+#[derive(Default)]
+struct RunOptions {
+    step_limit: Option<u64>,
+    detect_cycles: bool,
+    trace_prefix: Option<String>,
+    input_path: Option<String>,
+    accelerated: bool,
+    rule: Option<OffsetRule>,
+    benchmark_scale: Option<usize>,
+}
+
+fn options_from_args() -> RunOptions {
+    let mut options = RunOptions::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--step-limit" => options.step_limit = Some(args.next().and_then(|n| n.parse().ok()).expect("--step-limit expects a number")),
+            "--detect-cycles" => options.detect_cycles = true,
+            "--trace" => options.trace_prefix = Some(args.next().expect("--trace expects a file prefix")),
+            "--input" => options.input_path = Some(args.next().expect("--input expects a path")),
+            "--accelerated" => options.accelerated = true,
+            "--rule" => {
+                let source = args.next().expect("--rule expects an expression");
+                let rule = OffsetRule::parse(&source).unwrap_or_else(|e| panic!("Invalid rule: {}", e));
+                options.rule = Some(rule);
+            },
+            "--benchmark" => options.benchmark_scale = Some(args.next().and_then(|n| n.parse().ok()).expect("--benchmark expects how many times to repeat the maze")),
+            _ => panic!("Unrecognised argument {}", arg),
+        }
+    }
+    options
+}
+
+fn input_path(options: &RunOptions) -> &str {
+    options.input_path.as_ref().map_or(INPUT_PATH, |path| path)
+}
+
+// Rules are fallible so that a custom rule which divides by zero part way
+// through a run stops it with an outcome rather than a panic.
+fn steps_to_exit_with_rule<F: Fn(i64) -> Result<i64, EvaluationError>>(maze: &mut [i64], rule: F, options: &RunOptions, mut trace: Option<&mut Trace>) -> Outcome {
     let mut steps = 0;
     let mut current_idx: i64 = 0;
 
     loop {
+        if options.step_limit.is_some_and(|limit| steps >= limit) {
+            return Outcome::StepLimitReached(steps);
+        }
+
         let idx = current_idx as usize;
-        current_idx += maze[idx];
-        maze[idx] = rule(maze[idx]);
+        if let Some(ref mut trace) = trace {
+            trace.record(idx);
+        }
+        let offset = maze[idx];
+        maze[idx] = match rule(offset) {
+            Ok(updated) => updated,
+            Err(error) => return Outcome::RuleFailed { steps, offset, error },
+        };
+        current_idx += offset;
         steps += 1;
 
         if current_idx < 0 || current_idx >= maze.len() as i64 {
@@ -27,27 +95,61 @@ fn steps_to_exit_with_rule<F: Fn(i64) -> i64>(maze: &mut [i64], rule: F) -> u64
         }
     }
 
-    steps
+    Outcome::Exited(steps)
 }
 
+fn run_part<F: Fn(i64) -> Result<i64, EvaluationError>>(name: &str, trace_tag: &str, rule: F, options: &RunOptions) {
+    let mut maze = read_maze_from_file(input_path(options));
+    let mut trace = options.trace_prefix.as_ref().map(|_| Trace::new(maze.len()));
+    let outcome = if options.detect_cycles {
+        cycle::steps_to_exit_detecting_cycles(&mut maze, rule, options, trace.as_mut())
+    } else {
+        steps_to_exit_with_rule(&mut maze, rule, options, trace.as_mut())
+    };
 
+    match outcome {
+        Outcome::Exited(steps) => println!("The answer to {} is {}", name, steps),
+        Outcome::StepLimitReached(steps) => println!("The run of {} did not exit within {} steps", name, steps),
+        Outcome::Cycle { steps, cycle_start } => {
+            println!("The run of {} never exits: step {} repeats the state from step {}", name, steps, cycle_start)
+        },
+        Outcome::RuleFailed { steps, offset, error } => {
+            println!("The run of {} stopped after {} steps: {} when applying the rule to offset {}", name, steps, error, offset)
+        },
+    }
 
-fn part1() {
-    let mut maze = read_maze_from_file(INPUT_PATH);
-    let answer = steps_to_exit_with_rule(&mut maze, |offset| offset + 1);
-    println!("The answer to Part 1 is {}", answer);
+    if let (Some(trace), Some(prefix)) = (trace, options.trace_prefix.as_ref()) {
+        let prefix = format!("{}-{}", prefix, trace_tag);
+        trace.write_to_files(&prefix).expect("Unable to write trace");
+    }
 }
 
-fn part2() {
-    let mut maze = read_maze_from_file(INPUT_PATH);
-    let answer = steps_to_exit_with_rule(
-        &mut maze,
-        |offset| if offset >= 3 { offset - 1 } else { offset + 1 }
-    );
-    println!("The answer to Part 2 is {}", answer);
+fn part1(options: &RunOptions) {
+    run_part("Part 1", "part1", |offset| Ok(offset + 1), options);
+}
+
+fn part2(options: &RunOptions) {
+    if options.accelerated {
+        if options.step_limit.is_some() || options.detect_cycles || options.trace_prefix.is_some() {
+            panic!("--accelerated cannot be combined with --step-limit, --detect-cycles or --trace");
+        }
+        let mut maze = read_maze_from_file(input_path(options));
+        let answer = AcceleratedSolver::new().steps_to_exit(&mut maze);
+        println!("The answer to Part 2 is {}", answer);
+        return;
+    }
+
+    run_part("Part 2", "part2", |offset| Ok(if offset >= 3 { offset - 1 } else { offset + 1 }), options);
 }
 
 fn main() {
-    part1();
-    part2();
+    let options = options_from_args();
+    part1(&options);
+    part2(&options);
+    if let Some(ref rule) = options.rule {
+        run_part("the custom rule", "custom", |offset| rule.apply(offset), &options);
+    }
+    if let Some(scale) = options.benchmark_scale {
+        benchmark::run(&read_maze_from_file(input_path(&options)), scale);
+    }
 }
@@ -0,0 +1,433 @@
+use std::fmt;
+
+// A small expression language for offset rules, for example
+//
+//     if o >= 3 then o - 1 else o + 1
+//
+// where `o` is the offset being updated. Arithmetic is on i64 with the usual
+// precedence and wraps on overflow, including `i64::MIN / -1`. Dividing by
+// zero is an error: a divisor which is zero whatever the offset is rejected
+// when the rule is parsed, and any other is only found when the rule is
+// applied. Comparisons can be combined with `and`, `or` and `not`, and
+// `if ... then ... else ...` chooses between two expressions.
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvaluationError {
+    DivisionByZero,
+}
+
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EvaluationError::DivisionByZero => write!(f, "Division by zero"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Word(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &["<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "%", "(", ")"];
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while position < source.len() {
+        let rest = &source[position..];
+        let c = rest.chars().next().unwrap();
+
+        if c.is_whitespace() {
+            position += c.len_utf8();
+        } else if c.is_ascii_digit() {
+            let length = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let value = rest[..length].parse().map_err(|_| ParseError { position, message: "Number out of range".to_string() })?;
+            tokens.push((position, Token::Number(value)));
+            position += length;
+        } else if c.is_ascii_alphabetic() {
+            let length = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            tokens.push((position, Token::Word(rest[..length].to_string())));
+            position += length;
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+            tokens.push((position, Token::Symbol(symbol)));
+            position += symbol.len();
+        } else {
+            return Err(ParseError { position, message: format!("Unexpected character '{}'", c) });
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug)]
+enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Comparison {
+    LessThan,
+    LessThanEqual,
+    GreaterThan,
+    GreaterThanEqual,
+    EqualTo,
+    NotEqualTo,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Offset,
+    Constant(i64),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    If(Box<Condition>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug)]
+enum Condition {
+    Compare(Comparison, Expr, Expr),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Expr {
+    fn evaluate(&self, offset: i64) -> Result<i64, EvaluationError> {
+        Ok(match *self {
+            Expr::Offset => offset,
+            Expr::Constant(value) => value,
+            Expr::Negate(ref inner) => inner.evaluate(offset)?.wrapping_neg(),
+            Expr::Binary(op, ref lhs, ref rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(offset)?, rhs.evaluate(offset)?);
+                match op {
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Subtract => lhs.wrapping_sub(rhs),
+                    BinaryOp::Multiply => lhs.wrapping_mul(rhs),
+                    BinaryOp::Divide if rhs == 0 => return Err(EvaluationError::DivisionByZero),
+                    BinaryOp::Divide => lhs.wrapping_div(rhs),
+                    BinaryOp::Remainder if rhs == 0 => return Err(EvaluationError::DivisionByZero),
+                    BinaryOp::Remainder => lhs.wrapping_rem(rhs),
+                }
+            },
+            Expr::If(ref condition, ref then_expr, ref else_expr) => {
+                if condition.evaluate(offset)? { then_expr.evaluate(offset)? } else { else_expr.evaluate(offset)? }
+            },
+        })
+    }
+
+    // Whether the value is the same whatever the offset, so that it can be
+    // worked out when the rule is parsed.
+    fn is_constant(&self) -> bool {
+        match *self {
+            Expr::Offset => false,
+            Expr::Constant(_) => true,
+            Expr::Negate(ref inner) => inner.is_constant(),
+            Expr::Binary(_, ref lhs, ref rhs) => lhs.is_constant() && rhs.is_constant(),
+            Expr::If(ref condition, ref then_expr, ref else_expr) => {
+                condition.is_constant() && then_expr.is_constant() && else_expr.is_constant()
+            },
+        }
+    }
+}
+
+impl Condition {
+    fn evaluate(&self, offset: i64) -> Result<bool, EvaluationError> {
+        Ok(match *self {
+            Condition::Compare(comparison, ref lhs, ref rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(offset)?, rhs.evaluate(offset)?);
+                match comparison {
+                    Comparison::LessThan => lhs < rhs,
+                    Comparison::LessThanEqual => lhs <= rhs,
+                    Comparison::GreaterThan => lhs > rhs,
+                    Comparison::GreaterThanEqual => lhs >= rhs,
+                    Comparison::EqualTo => lhs == rhs,
+                    Comparison::NotEqualTo => lhs != rhs,
+                }
+            },
+            Condition::And(ref lhs, ref rhs) => lhs.evaluate(offset)? && rhs.evaluate(offset)?,
+            Condition::Or(ref lhs, ref rhs) => lhs.evaluate(offset)? || rhs.evaluate(offset)?,
+            Condition::Not(ref inner) => !inner.evaluate(offset)?,
+        })
+    }
+
+    fn is_constant(&self) -> bool {
+        match *self {
+            Condition::Compare(_, ref lhs, ref rhs) => lhs.is_constant() && rhs.is_constant(),
+            Condition::And(ref lhs, ref rhs) | Condition::Or(ref lhs, ref rhs) => lhs.is_constant() && rhs.is_constant(),
+            Condition::Not(ref inner) => inner.is_constant(),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |&(position, _)| position)
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, ParseError> {
+        Err(ParseError { position: self.position(), message: message.to_string() })
+    }
+
+    fn accept_word(&mut self, word: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(w)) if w == word => {},
+            _ => return false,
+        }
+        self.next += 1;
+        true
+    }
+
+    fn accept_symbol(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(&Token::Symbol(s)) if s == symbol => {},
+            _ => return false,
+        }
+        self.next += 1;
+        true
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), ParseError> {
+        if self.accept_word(word) { Ok(()) } else { self.error(&format!("Expected '{}'", word)) }
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        if self.accept_word("if") {
+            let condition = self.condition()?;
+            self.expect_word("then")?;
+            let then_expr = self.expr()?;
+            self.expect_word("else")?;
+            let else_expr = self.expr()?;
+            Ok(Expr::If(Box::new(condition), Box::new(then_expr), Box::new(else_expr)))
+        } else {
+            self.sum()
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.product()?;
+        loop {
+            let op = if self.accept_symbol("+") {
+                BinaryOp::Add
+            } else if self.accept_symbol("-") {
+                BinaryOp::Subtract
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.accept_symbol("*") {
+                BinaryOp::Multiply
+            } else if self.accept_symbol("/") {
+                BinaryOp::Divide
+            } else if self.accept_symbol("%") {
+                BinaryOp::Remainder
+            } else {
+                return Ok(lhs);
+            };
+
+            let position = self.position();
+            let rhs = self.unary()?;
+            let divides = matches!(op, BinaryOp::Divide | BinaryOp::Remainder);
+            // Only a divisor which does not depend on the offset can be
+            // checked here; any other is checked each time the rule is applied.
+            if divides && rhs.is_constant() && rhs.evaluate(0).map_or(true, |value| value == 0) {
+                return Err(ParseError { position, message: EvaluationError::DivisionByZero.to_string() });
+            }
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.accept_symbol("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+
+        let token = self.peek().cloned();
+        match token {
+            Some(Token::Number(value)) => {
+                self.next += 1;
+                Ok(Expr::Constant(value))
+            },
+            Some(Token::Word(ref word)) if word == "o" => {
+                self.next += 1;
+                Ok(Expr::Offset)
+            },
+            Some(Token::Symbol("(")) => {
+                self.next += 1;
+                let inner = self.expr()?;
+                if self.accept_symbol(")") { Ok(inner) } else { self.error("Expected ')'") }
+            },
+            _ => self.error("Expected a number, 'o' or '('"),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, ParseError> {
+        let mut lhs = self.conjunction()?;
+        while self.accept_word("or") {
+            lhs = Condition::Or(Box::new(lhs), Box::new(self.conjunction()?));
+        }
+        Ok(lhs)
+    }
+
+    fn conjunction(&mut self) -> Result<Condition, ParseError> {
+        let mut lhs = self.negation()?;
+        while self.accept_word("and") {
+            lhs = Condition::And(Box::new(lhs), Box::new(self.negation()?));
+        }
+        Ok(lhs)
+    }
+
+    fn negation(&mut self) -> Result<Condition, ParseError> {
+        if self.accept_word("not") {
+            return Ok(Condition::Not(Box::new(self.negation()?)));
+        }
+
+        // A parenthesis may open either a nested condition or an arithmetic
+        // expression on the left of a comparison, so try the former first.
+        let start = self.next;
+        if self.accept_symbol("(") {
+            if let Ok(condition) = self.condition() {
+                if self.accept_symbol(")") {
+                    return Ok(condition);
+                }
+            }
+            self.next = start;
+        }
+
+        let lhs = self.sum()?;
+        let comparison = if self.accept_symbol("<=") {
+            Comparison::LessThanEqual
+        } else if self.accept_symbol(">=") {
+            Comparison::GreaterThanEqual
+        } else if self.accept_symbol("==") {
+            Comparison::EqualTo
+        } else if self.accept_symbol("!=") {
+            Comparison::NotEqualTo
+        } else if self.accept_symbol("<") {
+            Comparison::LessThan
+        } else if self.accept_symbol(">") {
+            Comparison::GreaterThan
+        } else {
+            return self.error("Expected a comparison");
+        };
+        Ok(Condition::Compare(comparison, lhs, self.sum()?))
+    }
+}
+
+#[derive(Debug)]
+pub struct OffsetRule {
+    expr: Expr,
+}
+
+impl OffsetRule {
+    pub fn parse(source: &str) -> Result<OffsetRule, ParseError> {
+        let mut parser = Parser { tokens: tokenize(source)?, next: 0, end: source.len() };
+        let expr = parser.expr()?;
+        if parser.next < parser.tokens.len() {
+            return parser.error("Unexpected trailing input");
+        }
+        Ok(OffsetRule { expr })
+    }
+
+    pub fn apply(&self, offset: i64) -> Result<i64, EvaluationError> {
+        self.expr.evaluate(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(source: &str, offset: i64) -> Result<i64, EvaluationError> {
+        OffsetRule::parse(source).unwrap_or_else(|e| panic!("Invalid rule {}: {}", source, e)).apply(offset)
+    }
+
+    fn parse_error(source: &str) -> (usize, String) {
+        let e = OffsetRule::parse(source).expect_err(source);
+        (e.position, e.message)
+    }
+
+    #[test]
+    fn evaluates_with_the_usual_precedence() {
+        assert_eq!(apply("o + 2 * 3", 1), Ok(7));
+        assert_eq!(apply("(o + 2) * 3", 1), Ok(9));
+        assert_eq!(apply("-o - -1", 5), Ok(-4));
+        assert_eq!(apply("o - 7 % 4 / 2", 10), Ok(9));
+        assert_eq!(apply("if o >= 3 then o - 1 else o + 1", 3), Ok(2));
+        assert_eq!(apply("if o >= 3 then o - 1 else o + 1", 2), Ok(3));
+        assert_eq!(apply("if not (o < 0 or o == 5) and o != 2 then 1 else 0", 4), Ok(1));
+        assert_eq!(apply("if not (o < 0 or o == 5) and o != 2 then 1 else 0", 5), Ok(0));
+        assert_eq!(apply("if (o + 1) * 2 > 6 then 1 else 0", 3), Ok(1));
+    }
+
+    #[test]
+    fn reports_where_parsing_failed() {
+        assert_eq!(parse_error("o + $"), (4, "Unexpected character '$'".to_string()));
+        assert_eq!(parse_error("o +"), (3, "Expected a number, 'o' or '('".to_string()));
+        assert_eq!(parse_error("(o + 1"), (6, "Expected ')'".to_string()));
+        assert_eq!(parse_error("if o then 1 else 2"), (5, "Expected a comparison".to_string()));
+        assert_eq!(parse_error("if o > 1 else 2"), (9, "Expected 'then'".to_string()));
+        assert_eq!(parse_error("o 1"), (2, "Unexpected trailing input".to_string()));
+        assert_eq!(parse_error("99999999999999999999"), (0, "Number out of range".to_string()));
+    }
+
+    #[test]
+    fn rejects_divisors_which_are_always_zero() {
+        assert_eq!(parse_error("o / 0"), (4, "Division by zero".to_string()));
+        assert_eq!(parse_error("o % (1 - 1)"), (4, "Division by zero".to_string()));
+        assert_eq!(parse_error("o / (if 1 > 0 then 0 else 1)"), (4, "Division by zero".to_string()));
+        assert_eq!(parse_error("o / (1 / 0)"), (9, "Division by zero".to_string()));
+        assert_eq!(apply("o / (2 - 1)", 7), Ok(7));
+    }
+
+    #[test]
+    fn fails_when_a_divisor_turns_out_to_be_zero() {
+        assert_eq!(apply("o / (o - o)", 3), Err(EvaluationError::DivisionByZero));
+        assert_eq!(apply("o % (o - 2)", 2), Err(EvaluationError::DivisionByZero));
+        assert_eq!(apply("o % (o - 2)", 5), Ok(2));
+        assert_eq!(apply("if o / (o - 1) > 0 then 1 else 0", 1), Err(EvaluationError::DivisionByZero));
+    }
+
+    #[test]
+    fn wraps_on_overflow() {
+        assert_eq!(apply("o / -1", i64::MIN), Ok(i64::MIN));
+        assert_eq!(apply("o % -1", i64::MIN), Ok(0));
+        assert_eq!(apply("o + 1", i64::MAX), Ok(i64::MIN));
+        assert_eq!(apply("-o", i64::MIN), Ok(i64::MIN));
+    }
+}
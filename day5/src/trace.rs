@@ -0,0 +1,41 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+pub struct Trace {
+    path: Vec<usize>,
+    visits: Vec<u64>,
+}
+
+impl Trace {
+    pub fn new(maze_len: usize) -> Trace {
+        Trace { path: Vec::new(), visits: vec![0; maze_len] }
+    }
+
+    pub fn record(&mut self, idx: usize) {
+        self.path.push(idx);
+        self.visits[idx] += 1;
+    }
+
+    pub fn write_path_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "step,index")?;
+        for (step, idx) in self.path.iter().enumerate() {
+            writeln!(writer, "{},{}", step, idx)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_heatmap_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "index,visits")?;
+        for (idx, visits) in self.visits.iter().enumerate() {
+            writeln!(writer, "{},{}", idx, visits)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_to_files(&self, prefix: &str) -> io::Result<()> {
+        let mut path_file = BufWriter::new(File::create(format!("{}-path.csv", prefix))?);
+        self.write_path_csv(&mut path_file)?;
+        let mut heatmap_file = BufWriter::new(File::create(format!("{}-heatmap.csv", prefix))?);
+        self.write_heatmap_csv(&mut heatmap_file)
+    }
+}
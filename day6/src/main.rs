@@ -1,73 +1,320 @@
+extern crate cycles;
+
+use std::env;
+use std::fmt::{Debug, Display};
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::prelude::*;
-use std::collections::HashMap;
+use std::io::{self, BufWriter};
+use std::ops::{AddAssign, Div, Rem};
+use std::str::FromStr;
+use cycles::{Cycle, Strategy};
 
 const INPUT_PATH: &'static str = "inputs/input.txt";
 
-fn read_vector_from_file(path: &str) -> Vec<u64> {
+trait BlockCount: Copy + Ord + Hash + Default + Display + FromStr + AddAssign + Div<Output = Self> + Rem<Output = Self> {
+    fn from_usize(n: usize) -> Self;
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! impl_block_count {
+    ($($t:ty),*) => {
+        $(impl BlockCount for $t {
+            fn from_usize(n: usize) -> $t {
+                n as $t
+            }
+
+            fn to_usize(self) -> usize {
+                self as usize
+            }
+        })*
+    }
+}
+
+impl_block_count!(u64, u128);
+
+fn read_vector_from_file<T: BlockCount>(path: &str) -> Vec<T> where T::Err: Debug {
     let mut file = File::open(path).expect("Unable to open file");
     let mut contents = String::new();
     file.read_to_string(&mut contents).expect("Unable to read file");
 
-    contents.split_whitespace().map(|s| s.parse::<u64>().unwrap()).collect()
+    contents.split_whitespace().map(|s| s.parse::<T>().unwrap()).collect()
 }
 
-fn index_of_max(vector: &[u64]) -> usize {
-    let (mut best_value, mut best_idx) = (0, 0);
-    for (idx, &value) in vector.iter().enumerate() {
-        if value > best_value {
-            best_value = value;
-            best_idx = idx;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TieBreak {
+    Lowest,
+    Highest,
+    Random(u64),
+}
+
+// FNV-1a, used so that random tie-breaking is a fixed function of the seed and
+// the bank state. Redistribution stays deterministic, which cycle detection needs.
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100_0000_01b3);
         }
     }
+}
 
-    best_idx
+fn index_of_max<T: BlockCount>(vector: &[T], tie_break: TieBreak) -> usize {
+    let best_value = match vector.iter().max() {
+        Some(&value) => value,
+        None => return 0,
+    };
+    let mut best_indices = vector.iter().enumerate().filter(|&(_, &value)| value == best_value).map(|(idx, _)| idx);
+
+    match tie_break {
+        TieBreak::Lowest => best_indices.next().unwrap(),
+        TieBreak::Highest => best_indices.next_back().unwrap(),
+        TieBreak::Random(seed) => {
+            let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+            seed.hash(&mut hasher);
+            vector.hash(&mut hasher);
+            let candidates: Vec<usize> = best_indices.collect();
+            candidates[(hasher.finish() % candidates.len() as u64) as usize]
+        },
+    }
 }
 
-fn redistribute_blocks(memory_bank: &mut [u64]) {
-    let max_idx = index_of_max(memory_bank);
+// Every bank, including the one being emptied, receives an equal share of the
+// blocks, and the remainder goes one apiece to the banks following it.
+fn redistribute_blocks<T: BlockCount>(memory_bank: &mut [T], tie_break: TieBreak) {
+    let len = memory_bank.len();
+    let max_idx = index_of_max(memory_bank, tie_break);
     let blocks = memory_bank[max_idx];
-    memory_bank[max_idx] = 0;
-    let mut current_idx = max_idx;
-    for _ in 0..blocks {
-        current_idx = if current_idx + 1 == memory_bank.len() { 0 } else { current_idx + 1 };
-        memory_bank[current_idx] += 1;
-    }
-}
-
-fn period_of_redistribution(memory_bank: &[u64]) -> (usize, usize) {
-    let mut worker = memory_bank.to_vec();
-    let mut trials = 0;
-    let mut seen = HashMap::new();
-
-    loop {
-        if seen.contains_key(&worker) {
-            break;
-        } else {
-            seen.insert(worker.clone(), trials);
-            redistribute_blocks(&mut worker);
-            trials += 1;
+    memory_bank[max_idx] = T::default();
+
+    let share = blocks / T::from_usize(len);
+    let remainder = (blocks % T::from_usize(len)).to_usize();
+    for bank in memory_bank.iter_mut() {
+        *bank += share;
+    }
+    for offset in 1..remainder + 1 {
+        memory_bank[(max_idx + offset) % len] += T::from_usize(1);
+    }
+}
+
+fn redistribution_cycle<T: BlockCount>(memory_bank: &[T], options: &Options) -> Cycle {
+    cycles::detect(options.strategy, &memory_bank.to_vec(), |bank| {
+        let mut next = bank.clone();
+        redistribute_blocks(&mut next, options.tie_break);
+        next
+    })
+}
+
+fn period_of_redistribution<T: BlockCount>(memory_bank: &[T], options: &Options) -> (usize, usize) {
+    let cycle = redistribution_cycle(memory_bank, options);
+    (cycle.period, cycle.tail)
+}
+
+fn write_state_trace<T: BlockCount, W: Write>(memory_bank: &[T], options: &Options, writer: &mut W) -> io::Result<()> {
+    let cycle = redistribution_cycle(memory_bank, options);
+    let mut bank = memory_bank.to_vec();
+
+    for step in 0..cycle.tail + cycle.period + 1 {
+        let banks: Vec<String> = bank.iter().map(|blocks| blocks.to_string()).collect();
+        write!(writer, "{}: {}", step, banks.join(" "))?;
+        if step == cycle.tail {
+            write!(writer, " (loop starts)")?;
+        } else if step == cycle.tail + cycle.period {
+            write!(writer, " (repeats step {})", cycle.tail)?;
         }
+        writeln!(writer)?;
+        redistribute_blocks(&mut bank, options.tie_break);
     }
 
-    let period = seen.len() - seen.get(&worker).unwrap();
-    let tail = trials - period;
-    (period, tail)
+    Ok(())
+}
+
+struct Options {
+    strategy: Strategy,
+    input_path: String,
+    wide: bool,
+    tie_break: TieBreak,
+    trace_path: Option<String>,
 }
 
-fn part1() {
-    let memory_bank = read_vector_from_file(INPUT_PATH);
-    let (period, tail) = period_of_redistribution(&memory_bank);
+fn options_from_args() -> Options {
+    let mut options = Options {
+        strategy: Strategy::Brent,
+        input_path: INPUT_PATH.to_string(),
+        wide: false,
+        tie_break: TieBreak::Lowest,
+        trace_path: None,
+    };
+    let mut seed = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--strategy" => options.strategy = match args.next().as_deref() {
+                Some("floyd") => Strategy::Floyd,
+                Some("brent") => Strategy::Brent,
+                Some("hash") => Strategy::Hash,
+                _ => panic!("--strategy expects floyd, brent or hash"),
+            },
+            "--input" => options.input_path = args.next().expect("--input expects a path"),
+            "--u128" => options.wide = true,
+            "--ties" => options.tie_break = match args.next().as_deref() {
+                Some("lowest") => TieBreak::Lowest,
+                Some("highest") => TieBreak::Highest,
+                Some("random") => TieBreak::Random(0),
+                _ => panic!("--ties expects lowest, highest or random"),
+            },
+            "--seed" => seed = Some(args.next().and_then(|n| n.parse().ok()).expect("--seed expects a number")),
+            "--trace" => options.trace_path = Some(args.next().expect("--trace expects a path")),
+            _ => panic!("Unrecognised argument {}", arg),
+        }
+    }
+
+    // The seed only picks between tied banks at random, so it means nothing
+    // with the other tie-breaks.
+    match (options.tie_break, seed) {
+        (TieBreak::Random(_), Some(seed)) => options.tie_break = TieBreak::Random(seed),
+        (_, Some(_)) => panic!("--seed can only be used with --ties random"),
+        (_, None) => {},
+    }
+    options
+}
+
+fn part1<T: BlockCount>(options: &Options) where T::Err: Debug {
+    let memory_bank: Vec<T> = read_vector_from_file(&options.input_path);
+    let (period, tail) = period_of_redistribution(&memory_bank, options);
     println!("The answer to Part 1 is {}", tail + period);
 }
 
-fn part2() {
-    let memory_bank = read_vector_from_file(INPUT_PATH);
-    let (period, _) = period_of_redistribution(&memory_bank);
+fn part2<T: BlockCount>(options: &Options) where T::Err: Debug {
+    let memory_bank: Vec<T> = read_vector_from_file(&options.input_path);
+    let (period, _) = period_of_redistribution(&memory_bank, options);
     println!("The answer to Part 2 is {}", period);
 }
 
+fn trace<T: BlockCount>(options: &Options, path: &str) where T::Err: Debug {
+    let memory_bank: Vec<T> = read_vector_from_file(&options.input_path);
+    let mut writer = BufWriter::new(File::create(path).expect("Unable to create trace file"));
+    write_state_trace(&memory_bank, options, &mut writer).expect("Unable to write trace");
+}
+
+fn run<T: BlockCount>(options: &Options) where T::Err: Debug {
+    part1::<T>(options);
+    part2::<T>(options);
+    if let Some(ref path) = options.trace_path {
+        trace::<T>(options, path);
+    }
+}
+
 fn main() {
-    part1();
-    part2();
+    let options = options_from_args();
+    if options.wide {
+        run::<u128>(&options);
+    } else {
+        run::<u64>(&options);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Hands out the blocks one at a time, as the puzzle describes.
+    fn redistribute_one_by_one(memory_bank: &mut [u64], tie_break: TieBreak) {
+        let len = memory_bank.len();
+        let mut idx = index_of_max(memory_bank, tie_break);
+        let mut blocks = memory_bank[idx];
+        memory_bank[idx] = 0;
+        while blocks > 0 {
+            idx = (idx + 1) % len;
+            memory_bank[idx] += 1;
+            blocks -= 1;
+        }
+    }
+
+    #[test]
+    fn redistributes_like_handing_out_one_block_at_a_time() {
+        let mut seed = 0x853c_49e6_748f_ea9bu64;
+        let mut next = |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+
+        for _ in 0..2000 {
+            let len = next(7) as usize + 1;
+            let bank: Vec<u64> = (0..len).map(|_| next(25)).collect();
+            for &tie_break in &[TieBreak::Lowest, TieBreak::Highest, TieBreak::Random(7)] {
+                let (mut expected, mut actual) = (bank.clone(), bank.clone());
+                redistribute_one_by_one(&mut expected, tie_break);
+                redistribute_blocks(&mut actual, tie_break);
+                assert_eq!(actual, expected, "Redistributing {:?} with {:?}", bank, tie_break);
+            }
+        }
+    }
+
+    #[test]
+    fn wraps_the_remainder_and_refills_the_emptied_bank() {
+        let mut bank = vec![1u64, 2, 10, 0];
+        redistribute_blocks(&mut bank, TieBreak::Lowest);
+        assert_eq!(bank, vec![4, 4, 2, 3]);
+
+        let mut bank = vec![0u64, 2, 7, 0];
+        redistribute_blocks(&mut bank, TieBreak::Lowest);
+        assert_eq!(bank, vec![2, 4, 1, 2]);
+    }
+
+    fn options(tie_break: TieBreak) -> Options {
+        Options { strategy: Strategy::Brent, input_path: String::new(), wide: false, tie_break, trace_path: None }
+    }
+
+    #[test]
+    fn breaks_ties_as_asked() {
+        let bank = [3u64, 7, 1, 7, 7];
+        assert_eq!(index_of_max(&bank, TieBreak::Lowest), 1);
+        assert_eq!(index_of_max(&bank, TieBreak::Highest), 4);
+        assert_eq!(index_of_max(&[0u64, 0, 0], TieBreak::Lowest), 0);
+        assert_eq!(index_of_max(&[0u64, 0, 0], TieBreak::Highest), 2);
+        assert_eq!(index_of_max(&[] as &[u64], TieBreak::Lowest), 0);
+    }
+
+    #[test]
+    fn breaks_ties_at_random_as_a_function_of_the_seed_and_state() {
+        let bank = [7u64, 7, 7, 7, 7, 7, 7, 7];
+        for seed in 0..20 {
+            let choice = index_of_max(&bank, TieBreak::Random(seed));
+            assert_eq!(index_of_max(&bank, TieBreak::Random(seed)), choice);
+            assert_eq!(bank[choice], 7);
+        }
+        let choices: Vec<usize> = (0..20).map(|seed| index_of_max(&bank, TieBreak::Random(seed))).collect();
+        assert!(choices.iter().any(|&choice| choice != choices[0]));
+        assert_eq!(index_of_max(&[1u64, 9, 2], TieBreak::Random(3)), 1);
+        assert!(index_of_max(&[0u64, 0, 0], TieBreak::Random(3)) < 3);
+    }
+
+    #[test]
+    fn traces_the_example_up_to_its_first_repeat() {
+        let mut output = Vec::new();
+        write_state_trace(&[0u64, 2, 7, 0], &options(TieBreak::Lowest), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "0: 0 2 7 0\n\
+                                                       1: 2 4 1 2 (loop starts)\n\
+                                                       2: 3 1 2 3\n\
+                                                       3: 0 2 3 4\n\
+                                                       4: 1 3 4 1\n\
+                                                       5: 2 4 1 2 (repeats step 1)\n");
+        assert_eq!(period_of_redistribution(&[0u64, 2, 7, 0], &options(TieBreak::Lowest)), (4, 1));
+    }
+
+    #[test]
+    fn handles_block_counts_beyond_u64() {
+        let blocks = 3 * (1u128 << 64) + 2;
+        let mut bank = vec![5, blocks, 0];
+        redistribute_blocks(&mut bank, TieBreak::Lowest);
+        assert_eq!(bank, vec![5 + (1 << 64) + 1, 1 << 64, (1 << 64) + 1]);
+    }
 }
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use Tower;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fix {
+    pub name: String,
+    pub current_weight: u64,
+    pub proposed_weight: u64,
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} from {} to {}", self.name, self.current_weight, self.proposed_weight)
+    }
+}
+
+// The children of `parent` carry different subtower weights. Each option is a
+// minimal set of fixes which balances them; with more than one option there
+// is no way to tell from the tower which program is at fault.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Imbalance {
+    pub parent: String,
+    pub options: Vec<Vec<Fix>>,
+}
+
+impl Imbalance {
+    pub fn is_ambiguous(&self) -> bool {
+        self.options.len() > 1
+    }
+
+    pub fn is_fixable(&self) -> bool {
+        !self.options.is_empty()
+    }
+}
+
+// The possible subtower weights of a program once everything beneath it is
+// balanced. Several weights are possible when the program's own children are
+// ambiguously unbalanced, in which case they match up with that imbalance's
+// options.
+struct Balanced {
+    subtower_weights: Vec<u64>,
+    ambiguity: Option<usize>,
+}
+
+struct Balancer<'a> {
+    tower: &'a Tower,
+    imbalances: Vec<Imbalance>,
+}
+
+impl<'a> Balancer<'a> {
+    // An ambiguous child can often be settled by its siblings: pick the
+    // candidate weight that agrees with the most common weight among them.
+    fn resolve_ambiguities(&mut self, results: &mut [Balanced]) {
+        for idx in 0..results.len() {
+            let ambiguity = match results[idx].ambiguity {
+                Some(ambiguity) => ambiguity,
+                None => continue,
+            };
+
+            let mut sibling_counts = HashMap::new();
+            for (other_idx, other) in results.iter().enumerate() {
+                if other_idx != idx && other.subtower_weights.len() == 1 {
+                    *sibling_counts.entry(other.subtower_weights[0]).or_insert(0) += 1;
+                }
+            }
+
+            let chosen = results[idx].subtower_weights.iter()
+                .enumerate()
+                .filter(|&(_, weight)| sibling_counts.contains_key(weight))
+                .max_by_key(|&(_, weight)| sibling_counts[weight])
+                .map(|(option, _)| option);
+
+            if let Some(option) = chosen {
+                let kept = self.imbalances[ambiguity].options.swap_remove(option);
+                self.imbalances[ambiguity].options = vec![kept];
+                results[idx] = Balanced { subtower_weights: vec![results[idx].subtower_weights[option]], ambiguity: None };
+            }
+        }
+    }
+
+    fn balance(&mut self, name: &str) -> Balanced {
+        let tower = self.tower;
+        let children = tower.get_children(name);
+        let mut results: Vec<Balanced> = children.iter().map(|child| self.balance(child)).collect();
+
+        let untouched = children.iter().zip(results.iter())
+            .all(|(child, result)| result.subtower_weights == [tower.get_subtower_weight(child)]);
+        if untouched && !tower.is_unbalanced(name) {
+            return Balanced { subtower_weights: vec![tower.get_subtower_weight(name)], ambiguity: None };
+        }
+
+        self.resolve_ambiguities(&mut results);
+        let weights: Vec<u64> = results.iter().map(|result| result.subtower_weights[0]).collect();
+
+        let mut counts: HashMap<u64, usize> = HashMap::new();
+        for &weight in &weights {
+            *counts.entry(weight).or_insert(0) += 1;
+        }
+        let most_common = counts.values().cloned().max().unwrap_or(0);
+        if most_common == weights.len() {
+            let subtower_weight = tower.get_weight(name) + weights.iter().sum::<u64>();
+            return Balanced { subtower_weights: vec![subtower_weight], ambiguity: None };
+        }
+
+        let mut targets: Vec<u64> = counts.iter().filter(|&(_, &count)| count == most_common).map(|(&weight, _)| weight).collect();
+        targets.sort();
+
+        let mut options = Vec::new();
+        let mut subtower_weights = Vec::new();
+        for target in targets {
+            let fixes: Option<Vec<Fix>> = children.iter().zip(weights.iter())
+                .filter(|&(_, &weight)| weight != target)
+                .map(|(child, &weight)| {
+                    let current_weight = tower.get_weight(child);
+                    let proposed_weight = current_weight as i64 + target as i64 - weight as i64;
+                    if proposed_weight < 0 {
+                        None
+                    } else {
+                        Some(Fix { name: child.to_string(), current_weight, proposed_weight: proposed_weight as u64 })
+                    }
+                })
+                .collect();
+
+            if let Some(fixes) = fixes {
+                options.push(fixes);
+                subtower_weights.push(tower.get_weight(name) + target * children.len() as u64);
+            }
+        }
+
+        if subtower_weights.is_empty() {
+            subtower_weights.push(tower.get_weight(name) + weights.iter().sum::<u64>());
+        }
+        let ambiguity = if options.len() > 1 { Some(self.imbalances.len()) } else { None };
+        self.imbalances.push(Imbalance { parent: name.to_string(), options });
+
+        Balanced { subtower_weights, ambiguity }
+    }
+}
+
+pub fn find_imbalances(tower: &Tower) -> Vec<Imbalance> {
+    let mut balancer = Balancer { tower, imbalances: Vec::new() };
+    balancer.balance(tower.get_root());
+    balancer.imbalances
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use tests::example_tower;
+
+    fn tower(programs: &[(&str, u64, &[&str])]) -> Tower {
+        let weights: HashMap<String, u64> = programs.iter().map(|&(name, weight, _)| (name.to_string(), weight)).collect();
+        let children = programs.iter()
+            .map(|&(name, _, children)| (name.to_string(), children.iter().map(|c| c.to_string()).collect()))
+            .collect();
+        Tower::new(weights, children)
+    }
+
+    fn fix(name: &str, current_weight: u64, proposed_weight: u64) -> Fix {
+        Fix { name: name.to_string(), current_weight, proposed_weight }
+    }
+
+    #[test]
+    fn finds_the_single_fix_in_the_example() {
+        let tower = example_tower();
+        let imbalances = find_imbalances(&tower);
+        assert_eq!(imbalances, vec![Imbalance { parent: "tknk".to_string(), options: vec![vec![fix("ugml", 68, 60)]] }]);
+        assert!(imbalances[0].is_fixable() && !imbalances[0].is_ambiguous());
+    }
+
+    #[test]
+    fn offers_every_option_when_the_fault_is_ambiguous() {
+        let tower = tower(&[("root", 1, &["a", "b"]), ("a", 4, &[]), ("b", 6, &[])]);
+        let imbalances = find_imbalances(&tower);
+        assert_eq!(imbalances, vec![Imbalance {
+            parent: "root".to_string(),
+            options: vec![vec![fix("b", 6, 4)], vec![fix("a", 4, 6)]],
+        }]);
+        assert!(imbalances[0].is_ambiguous());
+    }
+
+    #[test]
+    fn settles_an_ambiguity_using_the_siblings() {
+        let tower = tower(&[
+            ("root", 1, &["a", "b", "c"]), ("a", 1, &["d", "e"]), ("d", 2, &[]), ("e", 3, &[]),
+            ("b", 7, &[]), ("c", 7, &[]),
+        ]);
+        assert_eq!(find_imbalances(&tower), vec![Imbalance { parent: "a".to_string(), options: vec![vec![fix("d", 2, 3)]] }]);
+    }
+
+    #[test]
+    fn fixes_several_children_at_once() {
+        let tower = tower(&[("root", 1, &["a", "b", "c", "d"]), ("a", 5, &[]), ("b", 5, &[]), ("c", 6, &[]), ("d", 7, &[])]);
+        assert_eq!(find_imbalances(&tower), vec![Imbalance {
+            parent: "root".to_string(),
+            options: vec![vec![fix("c", 6, 5), fix("d", 7, 5)]],
+        }]);
+    }
+
+    #[test]
+    fn reports_children_which_cannot_be_balanced() {
+        let tower = tower(&[
+            ("root", 1, &["a", "b", "c"]), ("a", 1, &["d", "e"]), ("d", 10, &[]), ("e", 10, &[]),
+            ("b", 5, &[]), ("c", 5, &[]),
+        ]);
+        let imbalances = find_imbalances(&tower);
+        assert_eq!(imbalances, vec![Imbalance { parent: "root".to_string(), options: vec![] }]);
+        assert!(!imbalances[0].is_fixable());
+    }
+
+    #[test]
+    fn finds_nothing_in_a_balanced_tower() {
+        let tower = tower(&[("root", 1, &["a", "b"]), ("a", 3, &[]), ("b", 3, &[])]);
+        assert_eq!(find_imbalances(&tower), vec![]);
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use {Tower, TowerItem};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EditError {
+    UnknownProgram(String),
+    DuplicateProgram(String),
+    RootEdit(String),
+    WouldCreateCycle { name: String, new_parent: String },
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EditError::UnknownProgram(ref name) => write!(f, "There is no program called {}", name),
+            EditError::DuplicateProgram(ref name) => write!(f, "{} is already in the tower", name),
+            EditError::RootEdit(ref name) => write!(f, "{} is the root and cannot be moved or removed", name),
+            EditError::WouldCreateCycle { ref name, ref new_parent } => write!(f, "{} is beneath {}, so it cannot hold it", new_parent, name),
+        }
+    }
+}
+
+impl Tower {
+    pub fn contains(&self, name: &str) -> bool {
+        self.items.contains_key(name)
+    }
+
+    fn check_known(&self, name: &str) -> Result<(), EditError> {
+        if self.contains(name) { Ok(()) } else { Err(EditError::UnknownProgram(name.to_string())) }
+    }
+
+    pub fn get_parent(&self, name: &str) -> Option<&str> {
+        self.parents.get(name).map(|parent| parent.as_str())
+    }
+
+    // Moves a child of `parent` from one subtower weight to another in the
+    // parent's counts, where `None` means the child is arriving or leaving,
+    // and updates whether the parent is balanced.
+    fn recount_child(&mut self, parent: &str, old_weight: Option<u64>, new_weight: Option<u64>) {
+        let counts = self.child_weight_counts.get_mut(parent).unwrap();
+        if let Some(weight) = old_weight {
+            let count = counts.get_mut(&weight).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&weight);
+            }
+        }
+        if let Some(weight) = new_weight {
+            *counts.entry(weight).or_insert(0) += 1;
+        }
+
+        if counts.len() > 1 {
+            self.unbalanced.insert(parent.to_string());
+        } else {
+            self.unbalanced.remove(parent);
+        }
+    }
+
+    // The subtower of `name` has just changed by `delta`, so every program
+    // above it changes by the same amount. Each one is recounted under its
+    // parent, which makes an edit O(depth) whatever the fan-out.
+    fn propagate(&mut self, name: &str, delta: i64) {
+        if delta == 0 {
+            return;
+        }
+        let mut current = name.to_string();
+        while let Some(parent) = self.parents.get(&current).cloned() {
+            let weight = self.get_subtower_weight(&current);
+            self.recount_child(&parent, Some((weight as i64 - delta) as u64), Some(weight));
+            self.items.get_mut(&parent).unwrap().adjust_subtower_weight(delta);
+            current = parent;
+        }
+    }
+
+    pub fn set_weight(&mut self, name: &str, weight: u64) -> Result<(), EditError> {
+        self.check_known(name)?;
+        let delta = weight as i64 - self.get_weight(name) as i64;
+        self.items.get_mut(name).unwrap().set_weight(weight);
+        self.propagate(name, delta);
+        Ok(())
+    }
+
+    pub fn add_program(&mut self, name: &str, weight: u64, parent: &str) -> Result<(), EditError> {
+        if self.contains(name) {
+            return Err(EditError::DuplicateProgram(name.to_string()));
+        }
+        self.check_known(parent)?;
+
+        self.items.insert(name.to_string(), TowerItem::new(weight, weight));
+        self.children.insert(name.to_string(), Vec::new());
+        self.child_weight_counts.insert(name.to_string(), HashMap::new());
+        self.children.get_mut(parent).unwrap().push(name.to_string());
+        self.parents.insert(name.to_string(), parent.to_string());
+        self.recount_child(parent, None, Some(weight));
+        self.items.get_mut(parent).unwrap().adjust_subtower_weight(weight as i64);
+        self.propagate(parent, weight as i64);
+        Ok(())
+    }
+
+    // Removes `name` together with everything it holds, returning the names
+    // of every program taken out of the tower.
+    pub fn remove_program(&mut self, name: &str) -> Result<Vec<String>, EditError> {
+        self.check_known(name)?;
+        let parent = match self.get_parent(name) {
+            Some(parent) => parent.to_string(),
+            None => return Err(EditError::RootEdit(name.to_string())),
+        };
+
+        let weight = self.get_subtower_weight(name);
+        self.children.get_mut(&parent).unwrap().retain(|child| child != name);
+        self.recount_child(&parent, Some(weight), None);
+
+        let mut removed = Vec::new();
+        let mut to_remove = vec![name.to_string()];
+        while let Some(item) = to_remove.pop() {
+            to_remove.extend(self.children.remove(&item).unwrap());
+            self.items.remove(&item);
+            self.parents.remove(&item);
+            self.child_weight_counts.remove(&item);
+            self.unbalanced.remove(&item);
+            removed.push(item);
+        }
+
+        self.items.get_mut(&parent).unwrap().adjust_subtower_weight(-(weight as i64));
+        self.propagate(&parent, -(weight as i64));
+        Ok(removed)
+    }
+
+    pub fn reparent(&mut self, name: &str, new_parent: &str) -> Result<(), EditError> {
+        self.check_known(name)?;
+        self.check_known(new_parent)?;
+        let old_parent = match self.get_parent(name) {
+            Some(parent) => parent.to_string(),
+            None => return Err(EditError::RootEdit(name.to_string())),
+        };
+        if self.path_from_root(new_parent).contains(&name) {
+            return Err(EditError::WouldCreateCycle { name: name.to_string(), new_parent: new_parent.to_string() });
+        }
+
+        let weight = self.get_subtower_weight(name);
+        self.children.get_mut(&old_parent).unwrap().retain(|child| child != name);
+        self.recount_child(&old_parent, Some(weight), None);
+        self.items.get_mut(&old_parent).unwrap().adjust_subtower_weight(-(weight as i64));
+        self.propagate(&old_parent, -(weight as i64));
+
+        self.children.get_mut(new_parent).unwrap().push(name.to_string());
+        self.parents.insert(name.to_string(), new_parent.to_string());
+        self.recount_child(new_parent, None, Some(weight));
+        self.items.get_mut(new_parent).unwrap().adjust_subtower_weight(weight as i64);
+        self.propagate(new_parent, weight as i64);
+        Ok(())
+    }
+
+    pub fn path_from_root(&self, name: &str) -> Vec<&str> {
+        let mut path = vec![self.items.get_key_value(name).unwrap().0.as_str()];
+        while let Some(parent) = self.get_parent(path[path.len() - 1]) {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    pub fn get_depth(&self, name: &str) -> usize {
+        self.path_from_root(name).len() - 1
+    }
+
+    pub fn lowest_common_ancestor(&self, a: &str, b: &str) -> &str {
+        let ancestors: HashSet<&str> = self.path_from_root(a).into_iter().collect();
+        let path = self.path_from_root(b);
+        path.into_iter().rev().find(|name| ancestors.contains(name)).unwrap()
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.unbalanced.is_empty()
+    }
+
+    pub fn unbalanced_programs(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.unbalanced.iter().map(|name| name.as_str()).collect();
+        names.sort();
+        names
+    }
+}
+
+fn parse_weight(word: &str) -> Result<u64, String> {
+    word.parse().map_err(|_| format!("{} is not a weight", word))
+}
+
+// Returns whatever the command has to report, if anything.
+fn run_command(tower: &mut Tower, line: &str) -> Result<Option<String>, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    // The queries expect every program they are given to exist.
+    if let Some(&command) = words.first() {
+        if ["weight", "path", "depth", "lca"].contains(&command) {
+            for name in &words[1..] {
+                tower.check_known(name).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    let output = match words.as_slice() {
+        [] => None,
+        ["set", name, weight] => {
+            tower.set_weight(name, parse_weight(weight)?).map_err(|e| e.to_string())?;
+            None
+        },
+        ["add", name, weight, parent] => {
+            tower.add_program(name, parse_weight(weight)?, parent).map_err(|e| e.to_string())?;
+            None
+        },
+        ["remove", name] => {
+            let removed = tower.remove_program(name).map_err(|e| e.to_string())?;
+            Some(format!("Removed {}", removed.join(", ")))
+        },
+        ["move", name, new_parent] => {
+            tower.reparent(name, new_parent).map_err(|e| e.to_string())?;
+            None
+        },
+        ["weight", name] => Some(format!("{} weighs {}, with a subtower of {}", name, tower.get_weight(name), tower.get_subtower_weight(name))),
+        ["path", name] => Some(tower.path_from_root(name).join(" -> ")),
+        ["depth", name] => Some(tower.get_depth(name).to_string()),
+        ["lca", a, b] => Some(tower.lowest_common_ancestor(a, b).to_string()),
+        ["status"] if tower.is_balanced() => Some("The tower is balanced".to_string()),
+        ["status"] => Some(format!("Unbalanced: {}", tower.unbalanced_programs().join(", "))),
+        _ => return Err(format!("Unrecognised command: {}", line.trim())),
+    };
+
+    Ok(output)
+}
+
+// Reads one command per line: set NAME WEIGHT, add NAME WEIGHT PARENT,
+// remove NAME, move NAME PARENT, weight NAME, path NAME, depth NAME,
+// lca NAME NAME or status. Bad commands are reported and skipped.
+pub fn run_commands<R: BufRead, W: Write>(tower: &mut Tower, reader: R, writer: &mut W) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        match run_command(tower, &line) {
+            Ok(Some(output)) => writeln!(writer, "{}", output)?,
+            Ok(None) => {},
+            Err(message) => writeln!(writer, "{}", message)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use tests::example_tower as example;
+
+    // Checks the incrementally maintained weights and balance against a
+    // tower built from scratch with the same programs.
+    fn assert_consistent(tower: &Tower) {
+        let weights: HashMap<String, u64> = tower.items.keys().map(|name| (name.clone(), tower.get_weight(name))).collect();
+        let rebuilt = Tower::new(weights, tower.children.clone());
+        for name in tower.items.keys() {
+            assert_eq!(tower.get_subtower_weight(name), rebuilt.get_subtower_weight(name), "Subtower weight of {}", name);
+            let mut child_weights: Vec<u64> = tower.get_children(name).iter().map(|c| tower.get_subtower_weight(c)).collect();
+            child_weights.sort();
+            child_weights.dedup();
+            assert_eq!(tower.is_unbalanced(name), child_weights.len() > 1, "Balance of {}", name);
+        }
+        assert_eq!(tower.unbalanced_programs(), rebuilt.unbalanced_programs());
+    }
+
+    #[test]
+    fn keeps_balance_up_to_date_through_edits() {
+        let mut tower = example();
+        assert_eq!(tower.unbalanced_programs(), vec!["tknk"]);
+
+        tower.set_weight("ugml", 60).unwrap();
+        assert!(tower.is_balanced());
+        assert_consistent(&tower);
+
+        tower.add_program("abcd", 5, "gyxo").unwrap();
+        assert_eq!(tower.unbalanced_programs(), vec!["tknk", "ugml"]);
+        assert_consistent(&tower);
+
+        tower.reparent("abcd", "ebii").unwrap();
+        tower.add_program("efgh", 5, "jptl").unwrap();
+        assert_eq!(tower.unbalanced_programs(), vec!["tknk", "ugml"]);
+        tower.add_program("ijkl", 5, "gyxo").unwrap();
+        assert_eq!(tower.unbalanced_programs(), vec!["tknk"]);
+        tower.set_weight("ugml", 45).unwrap();
+        assert!(tower.is_balanced());
+        assert_consistent(&tower);
+
+        assert_eq!(tower.remove_program("jptl").unwrap(), vec!["jptl", "efgh"]);
+        assert_eq!(tower.unbalanced_programs(), vec!["tknk"]);
+        assert_consistent(&tower);
+
+        tower.reparent("fwft", "padx").unwrap();
+        assert_consistent(&tower);
+    }
+
+    #[test]
+    fn rejects_impossible_edits() {
+        let mut tower = example();
+        assert_eq!(tower.set_weight("nope", 1), Err(EditError::UnknownProgram("nope".to_string())));
+        assert_eq!(tower.add_program("ugml", 1, "tknk"), Err(EditError::DuplicateProgram("ugml".to_string())));
+        assert_eq!(tower.remove_program("tknk"), Err(EditError::RootEdit("tknk".to_string())));
+        assert_eq!(tower.reparent("ugml", "gyxo"),
+                   Err(EditError::WouldCreateCycle { name: "ugml".to_string(), new_parent: "gyxo".to_string() }));
+        assert_consistent(&tower);
+    }
+
+    #[test]
+    fn runs_commands() {
+        let mut tower = example();
+        let commands = "status\nset ugml 60\nstatus\nweight ugml\npath cntj\ndepth cntj\nlca cntj qoyq\nfly away\n";
+        let mut output = Vec::new();
+        run_commands(&mut tower, commands.as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "Unbalanced: tknk\n\
+                                                       The tower is balanced\n\
+                                                       ugml weighs 60, with a subtower of 243\n\
+                                                       tknk -> fwft -> cntj\n\
+                                                       2\n\
+                                                       tknk\n\
+                                                       Unrecognised command: fly away\n");
+    }
+}
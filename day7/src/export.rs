@@ -0,0 +1,294 @@
+use std::io::{self, Write};
+
+use Tower;
+
+fn hidden_children(tower: &Tower, name: &str, depth: usize, max_depth: Option<usize>) -> usize {
+    match max_depth {
+        Some(max_depth) if depth >= max_depth => tower.get_children(name).len(),
+        _ => 0,
+    }
+}
+
+fn escape(name: &str) -> String {
+    name.chars().flat_map(|c| match c {
+        '"' | '\\' => vec!['\\', c],
+        _ => vec![c],
+    }).collect()
+}
+
+fn write_dot_node<W: Write>(tower: &Tower, name: &str, depth: usize, max_depth: Option<usize>, writer: &mut W) -> io::Result<()> {
+    let hidden = hidden_children(tower, name, depth, max_depth);
+    let mut label = format!("{}\\nweight {}\\nsubtower {}", escape(name), tower.get_weight(name), tower.get_subtower_weight(name));
+    if hidden > 0 {
+        label += &format!("\\n({} children hidden)", hidden);
+    }
+
+    let style = if tower.is_unbalanced(name) { ", style=filled, fillcolor=salmon" } else { "" };
+    writeln!(writer, "    \"{}\" [label=\"{}\"{}];", escape(name), label, style)?;
+
+    if hidden == 0 {
+        for child in tower.get_children(name) {
+            writeln!(writer, "    \"{}\" -> \"{}\";", escape(name), escape(child))?;
+            write_dot_node(tower, child, depth + 1, max_depth, writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_dot<W: Write>(tower: &Tower, max_depth: Option<usize>, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "digraph tower {{")?;
+    writeln!(writer, "    node [shape=box];")?;
+    write_dot_node(tower, tower.get_root(), 0, max_depth, writer)?;
+    writeln!(writer, "}}")
+}
+
+fn write_json_node<W: Write>(tower: &Tower, name: &str, depth: usize, max_depth: Option<usize>, writer: &mut W) -> io::Result<()> {
+    let indent = "  ".repeat(depth * 2 + 1);
+    let hidden = hidden_children(tower, name, depth, max_depth);
+
+    writeln!(writer, "{{")?;
+    writeln!(writer, "{}\"name\": \"{}\",", indent, escape(name))?;
+    writeln!(writer, "{}\"weight\": {},", indent, tower.get_weight(name))?;
+    writeln!(writer, "{}\"subtower_weight\": {},", indent, tower.get_subtower_weight(name))?;
+    writeln!(writer, "{}\"unbalanced\": {},", indent, tower.is_unbalanced(name))?;
+    writeln!(writer, "{}\"hidden_children\": {},", indent, hidden)?;
+
+    let children = if hidden == 0 { tower.get_children(name) } else { &[] };
+    if children.is_empty() {
+        writeln!(writer, "{}\"children\": []", indent)?;
+    } else {
+        writeln!(writer, "{}\"children\": [", indent)?;
+        for (idx, child) in children.iter().enumerate() {
+            write!(writer, "{}  ", indent)?;
+            write_json_node(tower, child, depth + 1, max_depth, writer)?;
+            writeln!(writer, "{}", if idx + 1 < children.len() { "," } else { "" })?;
+        }
+        writeln!(writer, "{}]", indent)?;
+    }
+
+    write!(writer, "{}}}", &indent[2..])
+}
+
+pub fn write_json<W: Write>(tower: &Tower, max_depth: Option<usize>, writer: &mut W) -> io::Result<()> {
+    write_json_node(tower, tower.get_root(), 0, max_depth, writer)?;
+    writeln!(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::example_tower;
+
+    fn dot(max_depth: Option<usize>) -> String {
+        let mut output = Vec::new();
+        write_dot(&example_tower(), max_depth, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn json(max_depth: Option<usize>) -> String {
+        let mut output = Vec::new();
+        write_json(&example_tower(), max_depth, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn writes_dot() {
+        assert_eq!(dot(None), r#"digraph tower {
+    node [shape=box];
+    "tknk" [label="tknk\nweight 41\nsubtower 778", style=filled, fillcolor=salmon];
+    "tknk" -> "ugml";
+    "ugml" [label="ugml\nweight 68\nsubtower 251"];
+    "ugml" -> "gyxo";
+    "gyxo" [label="gyxo\nweight 61\nsubtower 61"];
+    "ugml" -> "ebii";
+    "ebii" [label="ebii\nweight 61\nsubtower 61"];
+    "ugml" -> "jptl";
+    "jptl" [label="jptl\nweight 61\nsubtower 61"];
+    "tknk" -> "padx";
+    "padx" [label="padx\nweight 45\nsubtower 243"];
+    "padx" -> "pbga";
+    "pbga" [label="pbga\nweight 66\nsubtower 66"];
+    "padx" -> "havc";
+    "havc" [label="havc\nweight 66\nsubtower 66"];
+    "padx" -> "qoyq";
+    "qoyq" [label="qoyq\nweight 66\nsubtower 66"];
+    "tknk" -> "fwft";
+    "fwft" [label="fwft\nweight 72\nsubtower 243"];
+    "fwft" -> "ktlj";
+    "ktlj" [label="ktlj\nweight 57\nsubtower 57"];
+    "fwft" -> "cntj";
+    "cntj" [label="cntj\nweight 57\nsubtower 57"];
+    "fwft" -> "xhth";
+    "xhth" [label="xhth\nweight 57\nsubtower 57"];
+}
+"#);
+    }
+
+    #[test]
+    fn writes_dot_down_to_a_depth() {
+        assert_eq!(dot(Some(1)), r#"digraph tower {
+    node [shape=box];
+    "tknk" [label="tknk\nweight 41\nsubtower 778", style=filled, fillcolor=salmon];
+    "tknk" -> "ugml";
+    "ugml" [label="ugml\nweight 68\nsubtower 251\n(3 children hidden)"];
+    "tknk" -> "padx";
+    "padx" [label="padx\nweight 45\nsubtower 243\n(3 children hidden)"];
+    "tknk" -> "fwft";
+    "fwft" [label="fwft\nweight 72\nsubtower 243\n(3 children hidden)"];
+}
+"#);
+        assert!(dot(Some(0)).contains(r#""tknk" [label="tknk\nweight 41\nsubtower 778\n(3 children hidden)", style=filled, fillcolor=salmon];"#));
+    }
+
+    #[test]
+    fn writes_json() {
+        assert_eq!(json(None), r#"{
+  "name": "tknk",
+  "weight": 41,
+  "subtower_weight": 778,
+  "unbalanced": true,
+  "hidden_children": 0,
+  "children": [
+    {
+      "name": "ugml",
+      "weight": 68,
+      "subtower_weight": 251,
+      "unbalanced": false,
+      "hidden_children": 0,
+      "children": [
+        {
+          "name": "gyxo",
+          "weight": 61,
+          "subtower_weight": 61,
+          "unbalanced": false,
+          "hidden_children": 0,
+          "children": []
+        },
+        {
+          "name": "ebii",
+          "weight": 61,
+          "subtower_weight": 61,
+          "unbalanced": false,
+          "hidden_children": 0,
+          "children": []
+        },
+        {
+          "name": "jptl",
+          "weight": 61,
+          "subtower_weight": 61,
+          "unbalanced": false,
+          "hidden_children": 0,
+          "children": []
+        }
+      ]
+    },
+    {
+      "name": "padx",
+      "weight": 45,
+      "subtower_weight": 243,
+      "unbalanced": false,
+      "hidden_children": 0,
+      "children": [
+        {
+          "name": "pbga",
+          "weight": 66,
+          "subtower_weight": 66,
+          "unbalanced": false,
+          "hidden_children": 0,
+          "children": []
+        },
+        {
+          "name": "havc",
+          "weight": 66,
+          "subtower_weight": 66,
+          "unbalanced": false,
+          "hidden_children": 0,
+          "children": []
+        },
+        {
+          "name": "qoyq",
+          "weight": 66,
+          "subtower_weight": 66,
+          "unbalanced": false,
+          "hidden_children": 0,
+          "children": []
+        }
+      ]
+    },
+    {
+      "name": "fwft",
+      "weight": 72,
+      "subtower_weight": 243,
+      "unbalanced": false,
+      "hidden_children": 0,
+      "children": [
+        {
+          "name": "ktlj",
+          "weight": 57,
+          "subtower_weight": 57,
+          "unbalanced": false,
+          "hidden_children": 0,
+          "children": []
+        },
+        {
+          "name": "cntj",
+          "weight": 57,
+          "subtower_weight": 57,
+          "unbalanced": false,
+          "hidden_children": 0,
+          "children": []
+        },
+        {
+          "name": "xhth",
+          "weight": 57,
+          "subtower_weight": 57,
+          "unbalanced": false,
+          "hidden_children": 0,
+          "children": []
+        }
+      ]
+    }
+  ]
+}
+"#);
+    }
+
+    #[test]
+    fn writes_json_down_to_a_depth() {
+        assert_eq!(json(Some(1)), r#"{
+  "name": "tknk",
+  "weight": 41,
+  "subtower_weight": 778,
+  "unbalanced": true,
+  "hidden_children": 0,
+  "children": [
+    {
+      "name": "ugml",
+      "weight": 68,
+      "subtower_weight": 251,
+      "unbalanced": false,
+      "hidden_children": 3,
+      "children": []
+    },
+    {
+      "name": "padx",
+      "weight": 45,
+      "subtower_weight": 243,
+      "unbalanced": false,
+      "hidden_children": 3,
+      "children": []
+    },
+    {
+      "name": "fwft",
+      "weight": 72,
+      "subtower_weight": 243,
+      "unbalanced": false,
+      "hidden_children": 3,
+      "children": []
+    }
+  ]
+}
+"#);
+    }
+}
@@ -1,6 +1,15 @@
+mod balance;
+mod edit;
+mod export;
+mod validation;
+
+use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::collections::{HashMap};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::collections::{HashMap, HashSet};
+use std::process;
+use balance::Imbalance;
+use validation::{Problem, ProgramDefinition};
 
 const INPUT_PATH: &'static str = "inputs/input.txt";
 
@@ -24,12 +33,26 @@ impl TowerItem {
     fn get_subtower_weight(&self) -> u64 {
         self.subtower_weight
     }
+
+    fn set_weight(&mut self, weight: u64) {
+        self.subtower_weight = self.subtower_weight - self.weight + weight;
+        self.weight = weight;
+    }
+
+    fn adjust_subtower_weight(&mut self, delta: i64) {
+        self.subtower_weight = (self.subtower_weight as i64 + delta) as u64;
+    }
 }
 
 struct Tower {
     root: String,
     children: HashMap<String, Vec<String>>,
     items: HashMap<String, TowerItem>,
+    parents: HashMap<String, String>,
+    // For each program, how many of its children have each subtower weight,
+    // so that its balance can be kept up to date as weights change.
+    child_weight_counts: HashMap<String, HashMap<u64, usize>>,
+    unbalanced: HashSet<String>,
 }
 
 impl Tower {
@@ -57,11 +80,45 @@ impl Tower {
             items.insert(k.to_string(), TowerItem::new(*weights.get(k).unwrap(), *subtower_weights.get(k).unwrap()));
         }
 
-        Tower {
+        let mut parents = HashMap::new();
+        for (parent, parent_children) in &children {
+            for child in parent_children {
+                parents.insert(child.to_string(), parent.to_string());
+            }
+        }
+
+        let mut child_weight_counts = HashMap::new();
+        for (parent, parent_children) in &children {
+            let mut counts = HashMap::new();
+            for child in parent_children {
+                *counts.entry(items.get(child).unwrap().get_subtower_weight()).or_insert(0) += 1;
+            }
+            child_weight_counts.insert(parent.to_string(), counts);
+        }
+
+        let mut tower = Tower {
             root,
             children,
             items,
+            parents,
+            child_weight_counts,
+            unbalanced: HashSet::new(),
+        };
+        tower.unbalanced = tower.items.keys().filter(|k| tower.is_unbalanced(k)).cloned().collect();
+        tower
+    }
+
+    fn from_programs(programs: &[ProgramDefinition]) -> Result<Tower, Vec<Problem>> {
+        validation::validate(programs)?;
+
+        let mut weights = HashMap::new();
+        let mut children = HashMap::new();
+        for program in programs {
+            weights.insert(program.name.clone(), program.weight);
+            children.insert(program.name.clone(), program.children.clone());
         }
+
+        Ok(Tower::new(weights, children))
     }
 
     fn get_root(&self) -> &str {
@@ -81,33 +138,7 @@ impl Tower {
     }
 
     fn is_unbalanced(&self, name: &str) -> bool {
-        let child_weights: Vec<_> = self.get_children(name).iter()
-            .map(|c| self.get_subtower_weight(&c))
-            .collect();
-
-        if child_weights.len() < 2 {
-            false
-        } else {
-            child_weights.iter().any(|&c| c != child_weights[0])
-        }
-    }
-
-    fn has_unbalanced_children(&self, name: &str) -> bool {
-        self.get_children(name).iter().any(|c| self.is_unbalanced(c))
-    }
-
-    fn get_adjustment_needed_to_balance(&self) -> (&str, u64) {
-        let unbalanced_root = self.items.keys()
-            .filter(|k| self.is_unbalanced(k))
-            .filter(|k| !self.has_unbalanced_children(k))
-            .next().unwrap();
-
-        let children = self.get_children(unbalanced_root);
-
-        let bad_child = children.iter().find(|&c| !children.iter().any(|other| c != other && self.get_subtower_weight(other) == self.get_subtower_weight(c))).unwrap();
-        let other_child = children.iter().find(|&c| c != bad_child).unwrap();
-
-        (bad_child, self.get_weight(bad_child) + self.get_subtower_weight(other_child) - self.get_subtower_weight(bad_child))
+        self.child_weight_counts.get(name).unwrap().len() > 1
     }
 }
 
@@ -116,27 +147,35 @@ fn parse_weight(bracketed_weight: &str) -> u64 {
     bracketed_weight[1..len - 1].parse().unwrap()
 }
 
-fn read_tower_from_file(path: &str) -> Tower {
+fn read_programs_from_file(path: &str) -> Vec<ProgramDefinition> {
     let file = File::open(path).unwrap();
     let reader = BufReader::new(file);
 
-    let mut weights = HashMap::new();
-    let mut children = HashMap::new();
-
-    for line in reader.lines().map(|line| line.unwrap()) {
+    reader.lines().map(|line| line.unwrap()).map(|line| {
         let mut words = line.split_whitespace();
         let name = words.next().unwrap().to_string();
         let weight = parse_weight(words.next().unwrap());
-        let sub_programs: Vec<_> = words
+        let children: Vec<_> = words
             .map(|word| word.trim_matches(',').to_string())
             .filter(|ref word| word.chars().all(|c| c.is_alphabetic()))
             .collect();
 
-        weights.insert(name.clone(), weight);
-        children.insert(name, sub_programs);
-    }
+        ProgramDefinition { name, weight, children }
+    }).collect()
+}
 
-    Tower::new(weights, children)
+fn read_tower_from_file(path: &str) -> Tower {
+    let programs = read_programs_from_file(path);
+    match Tower::from_programs(&programs) {
+        Ok(tower) => tower,
+        Err(problems) => {
+            eprintln!("The tower in {} is invalid:", path);
+            for problem in problems {
+                eprintln!("  {}", problem);
+            }
+            process::exit(1);
+        },
+    }
 }
 
 fn part1() {
@@ -144,13 +183,150 @@ fn part1() {
     println!("The answer to Part 1 is {}", tower.get_root());
 }
 
+fn report_imbalances<W: Write>(imbalances: &[Imbalance], writer: &mut W) -> io::Result<()> {
+    match imbalances {
+        [] => writeln!(writer, "The tower is already balanced"),
+        [Imbalance { ref options, .. }] if options.len() == 1 && options[0].len() == 1 => {
+            writeln!(writer, "The answer to Part 2 is {}", options[0][0].proposed_weight)
+        },
+        _ => {
+            writeln!(writer, "The tower does not have exactly one wrong weight")?;
+            for imbalance in imbalances {
+                if !imbalance.is_fixable() {
+                    writeln!(writer, "  The children of {} cannot be balanced", imbalance.parent)?;
+                }
+                for option in &imbalance.options {
+                    let fixes: Vec<String> = option.iter().map(|fix| fix.to_string()).collect();
+                    let label = if imbalance.is_ambiguous() { "Possibly change" } else { "Change" };
+                    writeln!(writer, "  Under {}: {} {}", imbalance.parent, label, fixes.join(", "))?;
+                }
+            }
+            Ok(())
+        },
+    }
+}
+
 fn part2() {
     let tower = read_tower_from_file(INPUT_PATH);
-    let answer = tower.get_adjustment_needed_to_balance().1;
-    println!("The answer to Part 2 is {}", answer);
+    let imbalances = balance::find_imbalances(&tower);
+    let stdout = io::stdout();
+    report_imbalances(&imbalances, &mut stdout.lock()).expect("Unable to write to stdout");
+}
+
+struct Options {
+    dot_path: Option<String>,
+    json_path: Option<String>,
+    max_depth: Option<usize>,
+    interactive: bool,
+}
+
+fn options_from_args() -> Options {
+    let mut options = Options { dot_path: None, json_path: None, max_depth: None, interactive: false };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dot" => options.dot_path = Some(args.next().expect("--dot expects a path")),
+            "--json" => options.json_path = Some(args.next().expect("--json expects a path")),
+            "--depth" => options.max_depth = Some(args.next().and_then(|n| n.parse().ok()).expect("--depth expects a number")),
+            "--interactive" => options.interactive = true,
+            _ => panic!("Unrecognised argument {}", arg),
+        }
+    }
+    options
+}
+
+fn export(options: &Options) {
+    let tower = read_tower_from_file(INPUT_PATH);
+    if let Some(ref path) = options.dot_path {
+        let mut writer = BufWriter::new(File::create(path).expect("Unable to create file"));
+        export::write_dot(&tower, options.max_depth, &mut writer).expect("Unable to write DOT");
+    }
+    if let Some(ref path) = options.json_path {
+        let mut writer = BufWriter::new(File::create(path).expect("Unable to create file"));
+        export::write_json(&tower, options.max_depth, &mut writer).expect("Unable to write JSON");
+    }
+}
+
+fn interactive() {
+    let mut tower = read_tower_from_file(INPUT_PATH);
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    edit::run_commands(&mut tower, stdin.lock(), &mut stdout.lock()).expect("Unable to run commands");
 }
 
 fn main() {
+    let options = options_from_args();
     part1();
     part2();
+    export(&options);
+    if options.interactive {
+        interactive();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // The example from the puzzle, which is unbalanced at tknk.
+    pub fn example_tower() -> Tower {
+        let programs: &[(&str, u64, &[&str])] = &[
+            ("pbga", 66, &[]), ("xhth", 57, &[]), ("ebii", 61, &[]), ("havc", 66, &[]),
+            ("ktlj", 57, &[]), ("fwft", 72, &["ktlj", "cntj", "xhth"]), ("qoyq", 66, &[]),
+            ("padx", 45, &["pbga", "havc", "qoyq"]), ("tknk", 41, &["ugml", "padx", "fwft"]),
+            ("jptl", 61, &[]), ("ugml", 68, &["gyxo", "ebii", "jptl"]), ("gyxo", 61, &[]),
+            ("cntj", 57, &[]),
+        ];
+        let weights = programs.iter().map(|&(name, weight, _)| (name.to_string(), weight)).collect();
+        let children = programs.iter()
+            .map(|&(name, _, children)| (name.to_string(), children.iter().map(|c| c.to_string()).collect()))
+            .collect();
+        Tower::new(weights, children)
+    }
+
+    fn report(programs: &[(&str, u64, &[&str])]) -> String {
+        let programs: Vec<ProgramDefinition> = programs.iter()
+            .map(|&(name, weight, children)| ProgramDefinition {
+                name: name.to_string(),
+                weight,
+                children: children.iter().map(|c| c.to_string()).collect(),
+            })
+            .collect();
+        let tower = Tower::from_programs(&programs).unwrap_or_else(|_| panic!("Invalid tower"));
+        let mut output = Vec::new();
+        report_imbalances(&balance::find_imbalances(&tower), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn reports_the_single_wrong_weight() {
+        assert_eq!(report(&[("root", 1, &["a", "b", "c"]), ("a", 4, &[]), ("b", 4, &[]), ("c", 6, &[])]),
+                   "The answer to Part 2 is 4\n");
+    }
+
+    #[test]
+    fn reports_a_balanced_tower() {
+        assert_eq!(report(&[("root", 1, &["a", "b"]), ("a", 4, &[]), ("b", 4, &[])]), "The tower is already balanced\n");
+    }
+
+    #[test]
+    fn reports_children_which_cannot_be_balanced() {
+        let output = report(&[
+            ("root", 1, &["a", "b", "c"]), ("a", 1, &["d", "e"]), ("d", 10, &[]), ("e", 10, &[]),
+            ("b", 5, &[]), ("c", 5, &[]),
+        ]);
+        assert_eq!(output, "The tower does not have exactly one wrong weight\n  \
+                            The children of root cannot be balanced\n");
+    }
+
+    #[test]
+    fn reports_every_option_when_the_fault_is_ambiguous() {
+        assert_eq!(report(&[("root", 1, &["a", "b"]), ("a", 4, &[]), ("b", 6, &[])]),
+                   "The tower does not have exactly one wrong weight\n  \
+                    Under root: Possibly change b from 6 to 4\n  \
+                    Under root: Possibly change a from 4 to 6\n");
+        assert_eq!(report(&[("root", 1, &["a", "b", "c", "d"]), ("a", 5, &[]), ("b", 5, &[]), ("c", 6, &[]), ("d", 7, &[])]),
+                   "The tower does not have exactly one wrong weight\n  \
+                    Under root: Change c from 6 to 5, d from 7 to 5\n");
+    }
 }
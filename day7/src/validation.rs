@@ -0,0 +1,202 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+pub struct ProgramDefinition {
+    pub name: String,
+    pub weight: u64,
+    pub children: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    DuplicateDefinition(String),
+    UnknownChild { parent: String, child: String },
+    DuplicateChild { parent: String, child: String },
+    MultipleParents { child: String, parents: Vec<String> },
+    NoRoot,
+    MultipleRoots(Vec<String>),
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Problem::DuplicateDefinition(ref name) => write!(f, "{} is defined more than once", name),
+            Problem::UnknownChild { ref parent, ref child } => write!(f, "{} holds {}, which is never defined", parent, child),
+            Problem::DuplicateChild { ref parent, ref child } => write!(f, "{} holds {} more than once", parent, child),
+            Problem::MultipleParents { ref child, ref parents } => write!(f, "{} is held by more than one program: {}", child, parents.join(", ")),
+            Problem::NoRoot => write!(f, "Every program is held by another, so there is no root"),
+            Problem::MultipleRoots(ref roots) => write!(f, "There are several roots: {}", roots.join(", ")),
+            Problem::Cycle(ref names) => write!(f, "Programs hold each other in a cycle: {} -> {}", names.join(" -> "), names[0]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+fn find_cycles(children: &BTreeMap<&str, Vec<&str>>) -> Vec<Vec<String>> {
+    let mut cycles = Vec::new();
+    let mut visits: HashMap<&str, Visit> = HashMap::new();
+
+    for &start in children.keys() {
+        if visits.contains_key(start) {
+            continue;
+        }
+
+        // Depth-first search with an explicit stack of (node, next child index),
+        // so that the current path is available when a back edge is found.
+        let mut path: Vec<(&str, usize)> = vec![(start, 0)];
+        visits.insert(start, Visit::InProgress);
+        while !path.is_empty() {
+            let (node, next_child) = *path.last().unwrap();
+            let node_children = &children[node];
+            if next_child == node_children.len() {
+                visits.insert(node, Visit::Done);
+                path.pop();
+                continue;
+            }
+
+            path.last_mut().unwrap().1 += 1;
+            let child = node_children[next_child];
+            if !children.contains_key(child) {
+                continue;
+            }
+            match visits.get(child) {
+                None => {
+                    visits.insert(child, Visit::InProgress);
+                    path.push((child, 0));
+                },
+                Some(&Visit::InProgress) => {
+                    let cycle_start = path.iter().position(|&(name, _)| name == child).unwrap();
+                    cycles.push(path[cycle_start..].iter().map(|&(name, _)| name.to_string()).collect());
+                },
+                Some(&Visit::Done) => {},
+            }
+        }
+    }
+
+    cycles
+}
+
+pub fn validate(programs: &[ProgramDefinition]) -> Result<(), Vec<Problem>> {
+    let mut problems = Vec::new();
+
+    let mut children: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for program in programs {
+        if children.contains_key(program.name.as_str()) {
+            problems.push(Problem::DuplicateDefinition(program.name.clone()));
+        }
+        children.insert(&program.name, program.children.iter().map(|c| c.as_str()).collect());
+    }
+
+    let mut parents: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for (&parent, parent_children) in &children {
+        let mut repeated = BTreeSet::new();
+        for (idx, &child) in parent_children.iter().enumerate() {
+            if parent_children[..idx].contains(&child) {
+                if repeated.insert(child) {
+                    problems.push(Problem::DuplicateChild { parent: parent.to_string(), child: child.to_string() });
+                }
+                continue;
+            }
+            if !children.contains_key(child) {
+                problems.push(Problem::UnknownChild { parent: parent.to_string(), child: child.to_string() });
+            }
+            parents.entry(child).or_default().insert(parent);
+        }
+    }
+
+    for (&child, child_parents) in &parents {
+        if child_parents.len() > 1 {
+            let parents = child_parents.iter().map(|p| p.to_string()).collect();
+            problems.push(Problem::MultipleParents { child: child.to_string(), parents });
+        }
+    }
+
+    let roots: Vec<String> = children.keys()
+        .filter(|name| !parents.contains_key(*name))
+        .map(|name| name.to_string())
+        .collect();
+    match roots.len() {
+        0 => problems.push(Problem::NoRoot),
+        1 => {},
+        _ => problems.push(Problem::MultipleRoots(roots)),
+    }
+
+    problems.extend(find_cycles(&children).into_iter().map(Problem::Cycle));
+
+    if problems.is_empty() { Ok(()) } else { Err(problems) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn programs(definitions: &[(&str, &[&str])]) -> Vec<ProgramDefinition> {
+        definitions.iter()
+            .map(|&(name, children)| ProgramDefinition {
+                name: name.to_string(),
+                weight: 1,
+                children: children.iter().map(|c| c.to_string()).collect(),
+            })
+            .collect()
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn accepts_a_tree() {
+        assert_eq!(validate(&programs(&[("a", &["b", "c"]), ("b", &["d"]), ("c", &[]), ("d", &[])])), Ok(()));
+    }
+
+    #[test]
+    fn reports_unknown_children() {
+        assert_eq!(validate(&programs(&[("a", &["b", "x"]), ("b", &[])])),
+                   Err(vec![Problem::UnknownChild { parent: "a".to_string(), child: "x".to_string() }]));
+    }
+
+    #[test]
+    fn reports_duplicate_definitions() {
+        assert_eq!(validate(&programs(&[("a", &["b"]), ("b", &[]), ("b", &[])])),
+                   Err(vec![Problem::DuplicateDefinition("b".to_string())]));
+    }
+
+    #[test]
+    fn reports_children_held_twice_by_one_parent() {
+        assert_eq!(validate(&programs(&[("a", &["c", "b", "c", "c"]), ("b", &[]), ("c", &[])])),
+                   Err(vec![Problem::DuplicateChild { parent: "a".to_string(), child: "c".to_string() }]));
+        assert_eq!(Problem::DuplicateChild { parent: "a".to_string(), child: "c".to_string() }.to_string(), "a holds c more than once");
+    }
+
+    #[test]
+    fn reports_children_with_several_parents() {
+        assert_eq!(validate(&programs(&[("a", &["b", "c"]), ("b", &["d"]), ("c", &["d"]), ("d", &[])])),
+                   Err(vec![Problem::MultipleParents { child: "d".to_string(), parents: names(&["b", "c"]) }]));
+    }
+
+    #[test]
+    fn reports_a_missing_root() {
+        assert_eq!(validate(&programs(&[("a", &["b"]), ("b", &["a"])])),
+                   Err(vec![Problem::NoRoot, Problem::Cycle(names(&["a", "b"]))]));
+    }
+
+    #[test]
+    fn reports_several_roots() {
+        assert_eq!(validate(&programs(&[("a", &["c"]), ("b", &[]), ("c", &[]), ("d", &[])])),
+                   Err(vec![Problem::MultipleRoots(names(&["a", "b", "d"]))]));
+    }
+
+    #[test]
+    fn reports_cycles_beneath_the_root() {
+        let problems = validate(&programs(&[("root", &["a"]), ("a", &["b"]), ("b", &["c"]), ("c", &["a"])])).unwrap_err();
+        assert_eq!(problems, vec![Problem::MultipleParents { child: "a".to_string(), parents: names(&["c", "root"]) },
+                                  Problem::Cycle(names(&["a", "b", "c"]))]);
+        assert_eq!(problems[1].to_string(), "Programs hold each other in a cycle: a -> b -> c -> a");
+    }
+}
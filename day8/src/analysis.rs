@@ -0,0 +1,342 @@
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet};
+
+use {Condition, Instruction, Operand, ProgramLine};
+
+// The range of values a register might hold. Bounds are kept as i128 so that
+// arithmetic on them cannot overflow; a range which leaves i64 is widened to
+// cover every i64, since the CPU wraps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Range {
+    lo: i128,
+    hi: i128,
+}
+
+const FULL: Range = Range { lo: i64::MIN as i128, hi: i64::MAX as i128 };
+
+impl Range {
+    fn single(value: i64) -> Range {
+        Range { lo: value as i128, hi: value as i128 }
+    }
+
+    fn hull(values: &[i128]) -> Range {
+        let range = Range { lo: *values.iter().min().unwrap(), hi: *values.iter().max().unwrap() };
+        if range.lo < FULL.lo || range.hi > FULL.hi { FULL } else { range }
+    }
+
+    fn join(self, other: Range) -> Range {
+        Range { lo: cmp::min(self.lo, other.lo), hi: cmp::max(self.hi, other.hi) }
+    }
+
+    fn add(self, other: Range) -> Range {
+        Range::hull(&[self.lo + other.lo, self.hi + other.hi])
+    }
+
+    fn sub(self, other: Range) -> Range {
+        Range::hull(&[self.lo - other.hi, self.hi - other.lo])
+    }
+
+    fn mul(self, other: Range) -> Range {
+        Range::hull(&[self.lo * other.lo, self.lo * other.hi, self.hi * other.lo, self.hi * other.hi])
+    }
+
+    // Dividing by zero stops the program, so only the non-zero divisors
+    // matter. For a divisor of fixed sign the quotient is monotonic in both
+    // arguments, so the corners bound it.
+    fn div(self, other: Range) -> Option<Range> {
+        let mut corners = Vec::new();
+        for &(lo, hi) in &[(other.lo, cmp::min(other.hi, -1)), (cmp::max(other.lo, 1), other.hi)] {
+            if lo <= hi {
+                corners.extend_from_slice(&[self.lo / lo, self.lo / hi, self.hi / lo, self.hi / hi]);
+            }
+        }
+        if corners.is_empty() { None } else { Some(Range::hull(&corners)) }
+    }
+
+    // The remainder takes the sign of the dividend and is smaller in size than
+    // the divisor.
+    fn rem(self, other: Range) -> Option<Range> {
+        if other.lo == 0 && other.hi == 0 {
+            return None;
+        }
+        let limit = cmp::max(other.lo.abs(), other.hi.abs()) - 1;
+        let lo = if self.lo < 0 { cmp::max(self.lo, -limit) } else { 0 };
+        let hi = if self.hi > 0 { cmp::min(self.hi, limit) } else { 0 };
+        Some(Range { lo, hi })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Truth {
+    Always,
+    Never,
+    Sometimes,
+}
+
+impl Truth {
+    fn from_bounds(always: bool, never: bool) -> Truth {
+        if always { Truth::Always } else if never { Truth::Never } else { Truth::Sometimes }
+    }
+
+    fn not(self) -> Truth {
+        match self {
+            Truth::Always => Truth::Never,
+            Truth::Never => Truth::Always,
+            Truth::Sometimes => Truth::Sometimes,
+        }
+    }
+}
+
+fn less_than(a: Range, b: Range) -> Truth {
+    Truth::from_bounds(a.hi < b.lo, a.lo >= b.hi)
+}
+
+fn less_than_equal(a: Range, b: Range) -> Truth {
+    Truth::from_bounds(a.hi <= b.lo, a.lo > b.hi)
+}
+
+fn equal_to(a: Range, b: Range) -> Truth {
+    Truth::from_bounds(a.lo == a.hi && a == b, a.hi < b.lo || b.hi < a.lo)
+}
+
+struct Ranges {
+    ranges: BTreeMap<String, Range>,
+}
+
+impl Ranges {
+    fn get_register(&self, name: &str) -> Range {
+        *self.ranges.get(name).unwrap_or(&Range::single(0))
+    }
+
+    fn get(&self, operand: &Operand) -> Range {
+        match *operand {
+            Operand::Register(ref name) => self.get_register(name),
+            Operand::Constant(value) => Range::single(value),
+        }
+    }
+
+    fn truth(&self, condition: &Condition) -> Truth {
+        use Condition::*;
+
+        match *condition {
+            LessThan(ref a, ref b) => less_than(self.get(a), self.get(b)),
+            LessThanEqual(ref a, ref b) => less_than_equal(self.get(a), self.get(b)),
+            GreaterThan(ref a, ref b) => less_than(self.get(b), self.get(a)),
+            GreaterThanEqual(ref a, ref b) => less_than_equal(self.get(b), self.get(a)),
+            EqualTo(ref a, ref b) => equal_to(self.get(a), self.get(b)),
+            NotEqualTo(ref a, ref b) => equal_to(self.get(a), self.get(b)).not(),
+            And(ref a, ref b) => match (self.truth(a), self.truth(b)) {
+                (Truth::Never, _) | (_, Truth::Never) => Truth::Never,
+                (Truth::Always, Truth::Always) => Truth::Always,
+                _ => Truth::Sometimes,
+            },
+            Or(ref a, ref b) => match (self.truth(a), self.truth(b)) {
+                (Truth::Always, _) | (_, Truth::Always) => Truth::Always,
+                (Truth::Never, Truth::Never) => Truth::Never,
+                _ => Truth::Sometimes,
+            },
+            Not(ref a) => self.truth(a).not(),
+        }
+    }
+
+    // The register's range after the instruction, or `None` if it can only
+    // fail by dividing by zero.
+    fn apply(&self, instruction: &Instruction) -> Option<Range> {
+        use Instruction::*;
+
+        let current = self.get_register(instruction.get_register());
+        let amount = self.get(instruction.get_operand());
+        match *instruction {
+            Increment(..) => Some(current.add(amount)),
+            Decrement(..) => Some(current.sub(amount)),
+            Multiply(..) => Some(current.mul(amount)),
+            Divide(..) => current.div(amount),
+            Modulo(..) => current.rem(amount),
+            Set(..) => Some(amount),
+        }
+    }
+}
+
+pub struct Analysis {
+    // Each register mapped to the registers whose values it feeds into, either
+    // as an operand or through a condition guarding the write.
+    pub influences: BTreeMap<String, BTreeSet<String>>,
+    // Written, but their values never reach a condition, even indirectly.
+    pub dead_registers: BTreeSet<String>,
+    // Read, but never written, so always zero.
+    pub unwritten_registers: BTreeSet<String>,
+    // Line numbers, counting from 1.
+    pub never_true_lines: Vec<usize>,
+}
+
+pub fn analyse(program: &[ProgramLine]) -> Analysis {
+    let mut influences: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut written = BTreeSet::new();
+    let mut read = BTreeSet::new();
+    let mut read_in_conditions = BTreeSet::new();
+
+    for line in program {
+        let target = line.instruction.get_register();
+        written.insert(target.to_string());
+        let condition_registers = line.condition.get_registers();
+        let sources = condition_registers.iter().cloned().chain(line.instruction.get_operand().get_register());
+        for source in sources {
+            read.insert(source.to_string());
+            influences.entry(source.to_string()).or_default().insert(target.to_string());
+        }
+        read_in_conditions.extend(condition_registers.into_iter().map(|name| name.to_string()));
+    }
+
+    // A register is live if a condition reads it, or if it feeds into a live
+    // register.
+    let mut live = read_in_conditions;
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (source, targets) in &influences {
+            if !live.contains(source) && targets.iter().any(|target| live.contains(target)) {
+                live.insert(source.clone());
+                changed = true;
+            }
+        }
+    }
+
+    // The program runs straight through, so a single pass tracks every value
+    // each register could have reached by each line.
+    let mut ranges = Ranges { ranges: BTreeMap::new() };
+    let mut never_true_lines = Vec::new();
+    for (idx, line) in program.iter().enumerate() {
+        let truth = ranges.truth(&line.condition);
+        if truth == Truth::Never {
+            never_true_lines.push(idx + 1);
+            continue;
+        }
+
+        let name = line.instruction.get_register();
+        let before = ranges.get_register(name);
+        let after = match (ranges.apply(&line.instruction), truth) {
+            (None, _) => before,
+            (Some(after), Truth::Always) => after,
+            (Some(after), _) => after.join(before),
+        };
+        ranges.ranges.insert(name.to_string(), after);
+    }
+
+    Analysis {
+        influences,
+        dead_registers: written.iter().filter(|name| !live.contains(*name)).cloned().collect(),
+        unwritten_registers: read.difference(&written).cloned().collect(),
+        never_true_lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {parse_program, Cpu};
+
+    fn range(lo: i64, hi: i64) -> Range {
+        Range { lo: lo as i128, hi: hi as i128 }
+    }
+
+    fn program(lines: &[&str]) -> Vec<ProgramLine> {
+        parse_program(&lines.iter().map(|line| line.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn bounds_arithmetic_on_ranges() {
+        assert_eq!(range(1, 3).add(range(-5, 2)), range(-4, 5));
+        assert_eq!(range(1, 3).sub(range(-5, 2)), range(-1, 8));
+        assert_eq!(range(-2, 3).mul(range(-5, 4)), range(-15, 12));
+        assert_eq!(range(i64::MAX - 1, i64::MAX).add(range(0, 1)), FULL);
+        assert_eq!(range(i64::MIN, i64::MIN).mul(range(-1, -1)), FULL);
+    }
+
+    #[test]
+    fn skips_zero_when_dividing() {
+        assert_eq!(range(-10, 20).div(range(-2, 5)), Some(range(-20, 20)));
+        assert_eq!(range(10, 20).div(range(0, 5)), Some(range(2, 20)));
+        assert_eq!(range(10, 20).div(range(0, 0)), None);
+        assert_eq!(range(i64::MIN, i64::MIN).div(range(-1, -1)), Some(FULL));
+        assert_eq!(range(-7, 9).rem(range(-4, 2)), Some(range(-3, 3)));
+        assert_eq!(range(2, 9).rem(range(0, 5)), Some(range(0, 4)));
+        assert_eq!(range(-9, -2).rem(range(3, 3)), Some(range(-2, 0)));
+        assert_eq!(range(1, 1).rem(range(0, 0)), None);
+    }
+
+    #[test]
+    fn decides_comparisons_only_when_the_ranges_allow() {
+        assert_eq!(less_than(range(0, 2), range(3, 5)), Truth::Always);
+        assert_eq!(less_than(range(0, 3), range(3, 5)), Truth::Sometimes);
+        assert_eq!(less_than(range(3, 4), range(1, 3)), Truth::Never);
+        assert_eq!(less_than_equal(range(0, 3), range(3, 5)), Truth::Always);
+        assert_eq!(equal_to(range(2, 2), range(2, 2)), Truth::Always);
+        assert_eq!(equal_to(range(1, 2), range(1, 2)), Truth::Sometimes);
+        assert_eq!(equal_to(range(1, 2), range(3, 4)), Truth::Never);
+    }
+
+    #[test]
+    fn finds_lines_which_can_never_run() {
+        let analysis = analyse(&program(&[
+            "a inc 5 if b == 0",
+            "c inc 1 if a > 5",
+            "b dec 2 if a < 10 and c == 0",
+            "d set a if b > -3",
+            "d div b if d != 5",
+            "e mod d if not d == 5",
+            "c inc 1 if b < -2 or b > -2",
+        ]));
+        assert_eq!(analysis.never_true_lines, vec![2, 5, 6, 7]);
+    }
+
+    #[test]
+    fn follows_registers_through_conditions_and_operands() {
+        let analysis = analyse(&program(&[
+            "a inc b if c > 0",
+            "d inc a if e < 1",
+            "f inc 1 if d > 0",
+            "g inc 1 if f > 0",
+        ]));
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<BTreeSet<_>>();
+        assert_eq!(analysis.influences.get("b"), Some(&names(&["a"])));
+        assert_eq!(analysis.influences.get("c"), Some(&names(&["a"])));
+        assert_eq!(analysis.influences.get("a"), Some(&names(&["d"])));
+        assert_eq!(analysis.dead_registers, names(&["g"]));
+        assert_eq!(analysis.unwritten_registers, names(&["b", "c", "e"]));
+    }
+
+    // Every line which runs in practice must not be reported as never true.
+    #[test]
+    fn agrees_with_running_random_programs() {
+        let registers = ["a", "b", "c"];
+        let operations = ["inc", "dec", "mul", "div", "mod", "set"];
+        let comparisons = ["<", "<=", ">", ">=", "==", "!="];
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = |limit: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % limit as u64) as usize
+        };
+
+        for _ in 0..2000 {
+            let lines: Vec<String> = (0..next(8) + 1).map(|_| {
+                let operand = |n: usize| if n < 3 { registers[n].to_string() } else { (n as i64 - 6).to_string() };
+                format!("{} {} {} if {} {} {}", registers[next(3)], operations[next(6)], operand(next(10)),
+                        registers[next(3)], comparisons[next(6)], operand(next(10)))
+            }).collect();
+            let program = parse_program(&lines);
+            let analysis = analyse(&program);
+
+            let mut cpu = Cpu::new();
+            for (idx, line) in program.iter().enumerate() {
+                if cpu.is_condition_met(&line.condition) {
+                    assert!(!analysis.never_true_lines.contains(&(idx + 1)), "Line {} runs in {:?}", idx + 1, lines);
+                }
+                if cpu.apply_program_line(line).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
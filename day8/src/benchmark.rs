@@ -0,0 +1,112 @@
+use std::cmp;
+use std::time::{Duration, Instant};
+
+use bytecode::{self, Machine};
+use {parse_program, Cpu};
+
+// SplitMix64, so that the generated programs are the same on every run.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+const REGISTER_COUNT: u64 = 500;
+
+// Registers may not share their names with keywords, so they all get an
+// `r` prefix.
+fn register(rng: &mut SplitMix64) -> String {
+    let mut name = "r".to_string();
+    let mut rest = rng.below(REGISTER_COUNT);
+    loop {
+        name.push((b'a' + (rest % 26) as u8) as char);
+        rest /= 26;
+        if rest == 0 {
+            return name;
+        }
+    }
+}
+
+fn operand(rng: &mut SplitMix64) -> String {
+    if rng.below(4) == 0 {
+        register(rng)
+    } else {
+        (rng.below(2001) as i64 - 1000).to_string()
+    }
+}
+
+fn comparison(rng: &mut SplitMix64) -> String {
+    let comparisons = ["<", "<=", ">", ">=", "==", "!="];
+    let comparison = comparisons[rng.below(comparisons.len() as u64) as usize];
+    format!("{} {} {}", register(rng), comparison, operand(rng))
+}
+
+// Mostly lines in the style of the puzzle input, with a sprinkling of the
+// other operations and of compound conditions.
+fn generate_line(rng: &mut SplitMix64) -> String {
+    let instruction = match rng.below(20) {
+        0 => format!("{} mul {}", register(rng), rng.below(3) as i64 - 1),
+        1 => format!("{} div {}", register(rng), rng.below(9) + 1),
+        2 => format!("{} mod {}", register(rng), rng.below(999) + 1),
+        3 => format!("{} set {}", register(rng), operand(rng)),
+        n if n < 12 => format!("{} inc {}", register(rng), operand(rng)),
+        _ => format!("{} dec {}", register(rng), operand(rng)),
+    };
+    let condition = match rng.below(10) {
+        0 => format!("{} and {}", comparison(rng), comparison(rng)),
+        1 => format!("{} or not {}", comparison(rng), comparison(rng)),
+        _ => comparison(rng),
+    };
+    format!("{} if {}", instruction, condition)
+}
+
+fn time<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+}
+
+pub fn run(line_count: usize) {
+    let mut rng = SplitMix64(line_count as u64);
+    let lines: Vec<String> = (0..line_count).map(|_| generate_line(&mut rng)).collect();
+    let (program, parse_time) = time(|| parse_program(&lines));
+    println!("Generated and parsed {} lines in {:.1}ms", line_count, millis(parse_time));
+
+    let (_, interpret_time) = time(|| {
+        let mut cpu = Cpu::new();
+        let mut highest = i64::MIN;
+        for line in &program {
+            if let Some(value) = cpu.apply_program_line(line).unwrap() {
+                highest = cmp::max(highest, value);
+            }
+        }
+        (cpu.get_max_register_value(), highest)
+    });
+    println!("Interpreted in {:.1}ms", millis(interpret_time));
+
+    let (bytecode, compile_time) = time(|| bytecode::compile(&program));
+    let (_, run_time) = time(|| {
+        let mut machine = Machine::new(&bytecode);
+        let mut highest = i64::MIN;
+        machine.run(&bytecode, |_, value| highest = cmp::max(highest, value)).unwrap();
+        (machine.get_max_register_value().unwrap(), highest)
+    });
+    println!("Compiled in {:.1}ms and ran in {:.1}ms ({:.1}x faster than interpreting, {:.1}x including compilation)",
+             millis(compile_time), millis(run_time),
+             millis(interpret_time) / millis(run_time),
+             millis(interpret_time) / (millis(compile_time) + millis(run_time)));
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+use {Condition, ExecutionError, Operand, ProgramLine};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    LessThan,
+    LessThanEqual,
+    GreaterThan,
+    GreaterThanEqual,
+    EqualTo,
+    NotEqualTo,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operation {
+    Increment,
+    Decrement,
+    Multiply,
+    Divide,
+    Modulo,
+    Set,
+}
+
+// Operands are indices into the machine's slots. Registers and constants are
+// both interned into slots, so ops stay small and reading an operand never
+// needs to ask which kind it is; nothing ever updates a constant's slot.
+type Slot = u32;
+
+// Each program line becomes one `Test` per comparison in its condition,
+// followed by a single `Update`. Tests jump to the next test, the update or
+// past the update, so `and`, `or` and `not` short-circuit without any extra ops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Test { comparison: Comparison, a: Slot, b: Slot, then: u32, otherwise: u32 },
+    Update { operation: Operation, register: Slot, operand: Slot },
+}
+
+pub struct Bytecode {
+    ops: Vec<Op>,
+    initial_values: Vec<i64>,
+    // The register name for each slot, or `None` for a constant.
+    names: Vec<Option<String>>,
+}
+
+// FNV-1a. Interning hashes every operand of every line, and register names
+// are short, so this is much quicker than the default SipHash.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
+
+type FnvHashMap<K, V> = HashMap<K, V, BuildHasherDefault<Fnv>>;
+
+struct Compiler {
+    bytecode: Bytecode,
+    registers: FnvHashMap<String, Slot>,
+    constants: FnvHashMap<i64, Slot>,
+}
+
+fn comparison_count(condition: &Condition) -> u32 {
+    use Condition::*;
+
+    match *condition {
+        And(ref a, ref b) | Or(ref a, ref b) => comparison_count(a) + comparison_count(b),
+        Not(ref a) => comparison_count(a),
+        _ => 1,
+    }
+}
+
+impl Compiler {
+    fn new_slot(&mut self, initial_value: i64, name: Option<String>) -> Slot {
+        self.bytecode.initial_values.push(initial_value);
+        self.bytecode.names.push(name);
+        (self.bytecode.names.len() - 1) as Slot
+    }
+
+    fn register(&mut self, name: &str) -> Slot {
+        if let Some(&slot) = self.registers.get(name) {
+            return slot;
+        }
+        let slot = self.new_slot(0, Some(name.to_string()));
+        self.registers.insert(name.to_string(), slot);
+        slot
+    }
+
+    fn slot(&mut self, operand: &Operand) -> Slot {
+        match *operand {
+            Operand::Register(ref name) => self.register(name),
+            Operand::Constant(value) => {
+                if let Some(&slot) = self.constants.get(&value) {
+                    return slot;
+                }
+                let slot = self.new_slot(value, None);
+                self.constants.insert(value, slot);
+                slot
+            },
+        }
+    }
+
+    fn position(&self) -> u32 {
+        self.bytecode.ops.len() as u32
+    }
+
+    fn test(&mut self, comparison: Comparison, a: &Operand, b: &Operand, then: u32, otherwise: u32) {
+        let (a, b) = (self.slot(a), self.slot(b));
+        self.bytecode.ops.push(Op::Test { comparison, a, b, then, otherwise });
+    }
+
+    fn condition(&mut self, condition: &Condition, then: u32, otherwise: u32) {
+        use Condition::*;
+
+        match *condition {
+            LessThan(ref a, ref b) => self.test(Comparison::LessThan, a, b, then, otherwise),
+            LessThanEqual(ref a, ref b) => self.test(Comparison::LessThanEqual, a, b, then, otherwise),
+            GreaterThan(ref a, ref b) => self.test(Comparison::GreaterThan, a, b, then, otherwise),
+            GreaterThanEqual(ref a, ref b) => self.test(Comparison::GreaterThanEqual, a, b, then, otherwise),
+            EqualTo(ref a, ref b) => self.test(Comparison::EqualTo, a, b, then, otherwise),
+            NotEqualTo(ref a, ref b) => self.test(Comparison::NotEqualTo, a, b, then, otherwise),
+            And(ref a, ref b) => {
+                let next = self.position() + comparison_count(a);
+                self.condition(a, next, otherwise);
+                self.condition(b, then, otherwise);
+            },
+            Or(ref a, ref b) => {
+                let next = self.position() + comparison_count(a);
+                self.condition(a, then, next);
+                self.condition(b, then, otherwise);
+            },
+            Not(ref a) => self.condition(a, otherwise, then),
+        }
+    }
+
+    fn program_line(&mut self, line: &ProgramLine) {
+        use Instruction::*;
+
+        let update = self.position() + comparison_count(&line.condition);
+        self.condition(&line.condition, update, update + 1);
+
+        let (operation, name, operand) = match line.instruction {
+            Increment(ref name, ref operand) => (Operation::Increment, name, operand),
+            Decrement(ref name, ref operand) => (Operation::Decrement, name, operand),
+            Multiply(ref name, ref operand) => (Operation::Multiply, name, operand),
+            Divide(ref name, ref operand) => (Operation::Divide, name, operand),
+            Modulo(ref name, ref operand) => (Operation::Modulo, name, operand),
+            Set(ref name, ref operand) => (Operation::Set, name, operand),
+        };
+        let register = self.register(name);
+        let operand = self.slot(operand);
+        self.bytecode.ops.push(Op::Update { operation, register, operand });
+    }
+}
+
+pub fn compile(program: &[ProgramLine]) -> Bytecode {
+    let bytecode = Bytecode { ops: Vec::with_capacity(program.len()), initial_values: Vec::new(), names: Vec::new() };
+    let mut compiler = Compiler { bytecode, registers: FnvHashMap::default(), constants: FnvHashMap::default() };
+    for line in program {
+        compiler.program_line(line);
+    }
+    compiler.bytecode
+}
+
+pub struct Machine {
+    slots: Vec<i64>,
+    written: Vec<bool>,
+}
+
+impl Machine {
+    pub fn new(bytecode: &Bytecode) -> Machine {
+        Machine { slots: bytecode.initial_values.clone(), written: vec![false; bytecode.names.len()] }
+    }
+
+    // Runs the whole program, calling `on_update` with the register's slot and
+    // its new value after every update which goes ahead.
+    pub fn run<F: FnMut(Slot, i64)>(&mut self, bytecode: &Bytecode, mut on_update: F) -> Result<(), ExecutionError> {
+        let ops = &bytecode.ops;
+        let mut pc = 0;
+        while pc < ops.len() {
+            match ops[pc] {
+                Op::Test { comparison, a, b, then, otherwise } => {
+                    let (a, b) = (self.slots[a as usize], self.slots[b as usize]);
+                    let met = match comparison {
+                        Comparison::LessThan => a < b,
+                        Comparison::LessThanEqual => a <= b,
+                        Comparison::GreaterThan => a > b,
+                        Comparison::GreaterThanEqual => a >= b,
+                        Comparison::EqualTo => a == b,
+                        Comparison::NotEqualTo => a != b,
+                    };
+                    pc = if met { then } else { otherwise } as usize;
+                },
+                Op::Update { operation, register, operand } => {
+                    let amount = self.slots[operand as usize];
+                    let current = self.slots[register as usize];
+                    let value = match operation {
+                        Operation::Increment => current.wrapping_add(amount),
+                        Operation::Decrement => current.wrapping_sub(amount),
+                        Operation::Multiply => current.wrapping_mul(amount),
+                        Operation::Divide | Operation::Modulo if amount == 0 => {
+                            let name = bytecode.names[register as usize].clone().unwrap();
+                            return Err(ExecutionError::DivisionByZero(name));
+                        },
+                        Operation::Divide => current.wrapping_div(amount),
+                        Operation::Modulo => current.wrapping_rem(amount),
+                        Operation::Set => amount,
+                    };
+                    self.slots[register as usize] = value;
+                    self.written[register as usize] = true;
+                    on_update(register, value);
+                    pc += 1;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // Like `Cpu`, only registers which have been updated count.
+    pub fn get_max_register_value(&self) -> Option<i64> {
+        self.slots.iter().zip(self.written.iter())
+            .filter(|&(_, &written)| written)
+            .map(|(&value, _)| value)
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp;
+    use super::*;
+    use {parse_program, Cpu};
+
+    // The final value of every register written, the highest value written
+    // along the way, and how the run ended.
+    type Run = (Vec<(String, i64)>, Option<i64>, Result<(), ExecutionError>);
+
+    fn interpret(program: &[ProgramLine]) -> Run {
+        let mut cpu = Cpu::new();
+        let mut highest = None;
+        let mut result = Ok(());
+        for line in program {
+            match cpu.apply_program_line(line) {
+                Ok(Some(value)) => highest = cmp::max(highest, Some(value)),
+                Ok(None) => {},
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                },
+            }
+        }
+        let mut registers: Vec<(String, i64)> = cpu.registers.into_iter().collect();
+        registers.sort();
+        (registers, highest, result)
+    }
+
+    fn execute(program: &[ProgramLine]) -> Run {
+        let bytecode = compile(program);
+        let mut machine = Machine::new(&bytecode);
+        let mut highest = None;
+        let result = machine.run(&bytecode, |_, value| highest = cmp::max(highest, Some(value)));
+        let mut registers: Vec<(String, i64)> = bytecode.names.iter().zip(machine.slots.iter()).zip(machine.written.iter())
+            .filter(|&(_, &written)| written)
+            .map(|((name, &value), _)| (name.clone().unwrap(), value))
+            .collect();
+        registers.sort();
+        (registers, highest, result)
+    }
+
+    fn check(lines: &[String]) {
+        let program = parse_program(lines);
+        assert_eq!(execute(&program), interpret(&program), "Disagreement on {:?}", lines);
+    }
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn short_circuits_compound_conditions() {
+        let conditions = ["not (a > 0 and b > 0) or c == 1", "a > 0 and (b > 0 or not c == 1)", "not not a > 0",
+                          "(a > 0 or b > 0) and (c == 1 or a == 2)", "not (a > 0 or b > 0 or c == 1)",
+                          "a > 0 and b > 0 and not c == 1 or a < b"];
+        for &(a, b, c) in &[(0, 0, 0), (1, 0, 0), (0, 1, 1), (1, 1, 0), (1, 1, 1), (2, 0, 1)] {
+            for condition in &conditions {
+                check(&[format!("a set {} if z == 0", a), format!("b set {} if z == 0", b), format!("c set {} if z == 0", c),
+                        format!("d inc 1 if {}", condition)]);
+            }
+        }
+    }
+
+    #[test]
+    fn stops_on_division_by_zero() {
+        let program = lines(&["a inc 5 if b == 0", "b div a if a > 0", "a mod b if not a == 0", "c inc 1 if a > 0"]);
+        check(&program);
+        let (_, _, result) = execute(&parse_program(&program));
+        assert_eq!(result, Err(ExecutionError::DivisionByZero("a".to_string())));
+    }
+
+    #[test]
+    fn agrees_with_the_interpreter_on_random_programs() {
+        let registers = ["a", "b", "c"];
+        let operations = ["inc", "dec", "mul", "div", "mod", "set"];
+        let comparisons = ["<", "<=", ">", ">=", "==", "!="];
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = |limit: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % limit as u64) as usize
+        };
+
+        for _ in 0..2000 {
+            let program: Vec<String> = (0..next(10) + 1).map(|_| {
+                let operand = |n: usize| if n < 3 { registers[n].to_string() } else { (n as i64 - 5).to_string() };
+                let mut condition = format!("{} {} {}", registers[next(3)], comparisons[next(6)], operand(next(9)));
+                for _ in 0..next(4) {
+                    let comparison = format!("{} {} {}", registers[next(3)], comparisons[next(6)], operand(next(9)));
+                    condition = match next(4) {
+                        0 => format!("({}) and {}", condition, comparison),
+                        1 => format!("{} or ({})", comparison, condition),
+                        2 => format!("not ({}) and {}", condition, comparison),
+                        _ => format!("not {} or {}", comparison, condition),
+                    };
+                }
+                format!("{} {} {} if {}", registers[next(3)], operations[next(6)], operand(next(9)), condition)
+            }).collect();
+            check(&program);
+        }
+    }
+}
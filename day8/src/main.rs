@@ -2,26 +2,101 @@ extern crate regex;
 #[macro_use]
 extern crate lazy_static;
 
+mod analysis;
+mod benchmark;
+mod bytecode;
+mod recorder;
+
+use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter};
 use std::collections::{HashMap};
 use std::cmp;
+use std::fmt;
 use regex::Regex;
+use bytecode::Machine;
 
 const INPUT_PATH: &'static str = "inputs/input.txt";
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Operand {
+    Register(String),
+    Constant(i64),
+}
+
 enum Instruction {
-    Increment(String, i64),
-    Decrement(String, i64),
+    Increment(String, Operand),
+    Decrement(String, Operand),
+    Multiply(String, Operand),
+    Divide(String, Operand),
+    Modulo(String, Operand),
+    Set(String, Operand),
 }
 
 enum Condition {
-    LessThan(String, i64),
-    LessThanEqual(String, i64),
-    GreaterThan(String, i64),
-    GreaterThanEqual(String, i64),
-    EqualTo(String, i64),
-    NotEqualTo(String, i64),
+    LessThan(Operand, Operand),
+    LessThanEqual(Operand, Operand),
+    GreaterThan(Operand, Operand),
+    GreaterThanEqual(Operand, Operand),
+    EqualTo(Operand, Operand),
+    NotEqualTo(Operand, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Operand {
+    fn get_register(&self) -> Option<&str> {
+        match *self {
+            Operand::Register(ref name) => Some(name),
+            Operand::Constant(_) => None,
+        }
+    }
+}
+
+impl Instruction {
+    fn get_register(&self) -> &str {
+        use Instruction::*;
+
+        match *self {
+            Increment(ref name, _) | Decrement(ref name, _) | Multiply(ref name, _) |
+            Divide(ref name, _) | Modulo(ref name, _) | Set(ref name, _) => name,
+        }
+    }
+
+    fn get_operand(&self) -> &Operand {
+        use Instruction::*;
+
+        match *self {
+            Increment(_, ref operand) | Decrement(_, ref operand) | Multiply(_, ref operand) |
+            Divide(_, ref operand) | Modulo(_, ref operand) | Set(_, ref operand) => operand,
+        }
+    }
+}
+
+impl Condition {
+    fn collect_registers<'a>(&'a self, registers: &mut Vec<&'a str>) {
+        use Condition::*;
+
+        match *self {
+            LessThan(ref a, ref b) | LessThanEqual(ref a, ref b) | GreaterThan(ref a, ref b) |
+            GreaterThanEqual(ref a, ref b) | EqualTo(ref a, ref b) | NotEqualTo(ref a, ref b) => {
+                registers.extend(a.get_register());
+                registers.extend(b.get_register());
+            },
+            And(ref a, ref b) | Or(ref a, ref b) => {
+                a.collect_registers(registers);
+                b.collect_registers(registers);
+            },
+            Not(ref a) => a.collect_registers(registers),
+        }
+    }
+
+    fn get_registers(&self) -> Vec<&str> {
+        let mut registers = Vec::new();
+        self.collect_registers(&mut registers);
+        registers
+    }
 }
 
 struct ProgramLine {
@@ -29,6 +104,19 @@ struct ProgramLine {
     pub condition: Condition,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+enum ExecutionError {
+    DivisionByZero(String),
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExecutionError::DivisionByZero(ref name) => write!(f, "Division by zero while updating register {}", name),
+        }
+    }
+}
+
 struct Cpu {
     registers: HashMap<String, i64>,
 }
@@ -46,88 +134,185 @@ impl Cpu {
         *self.registers.get(name).unwrap_or(&0)
     }
 
-    fn increase_register_value(&mut self, name: &str, amount: i64) -> i64 {
-        let register = self.get_register_mut(name);
-        *register += amount;
-        *register
+    fn get_operand_value(&self, operand: &Operand) -> i64 {
+        match *operand {
+            Operand::Register(ref name) => self.get_register_value(name),
+            Operand::Constant(value) => value,
+        }
     }
 
-    fn decrease_register_value(&mut self, name: &str, amount: i64) -> i64 {
-        let register = self.get_register_mut(name);
-        *register -= amount;
-        *register
+    // Arithmetic wraps on overflow; `div` and `mod` truncate towards zero.
+    fn update_register<F: Fn(i64, i64) -> Option<i64>>(&mut self, name: &str, operand: &Operand, f: F) -> Result<i64, ExecutionError> {
+        let amount = self.get_operand_value(operand);
+        // A failed update leaves the register untouched, as the compiled
+        // machine does.
+        let value = f(self.get_register_value(name), amount).ok_or_else(|| ExecutionError::DivisionByZero(name.to_string()))?;
+        *self.get_register_mut(name) = value;
+        Ok(value)
     }
 
     fn get_max_register_value(&self) -> i64 {
         *self.registers.values().max().unwrap()
     }
 
-    fn apply_program_line(&mut self, line: &ProgramLine) -> Option<i64> {
+    fn is_condition_met(&self, condition: &Condition) -> bool {
         use Condition::*;
+
+        match *condition {
+            LessThan(ref a, ref b) => self.get_operand_value(a) < self.get_operand_value(b),
+            LessThanEqual(ref a, ref b) => self.get_operand_value(a) <= self.get_operand_value(b),
+            GreaterThan(ref a, ref b) => self.get_operand_value(a) > self.get_operand_value(b),
+            GreaterThanEqual(ref a, ref b) => self.get_operand_value(a) >= self.get_operand_value(b),
+            EqualTo(ref a, ref b) => self.get_operand_value(a) == self.get_operand_value(b),
+            NotEqualTo(ref a, ref b) => self.get_operand_value(a) != self.get_operand_value(b),
+            And(ref a, ref b) => self.is_condition_met(a) && self.is_condition_met(b),
+            Or(ref a, ref b) => self.is_condition_met(a) || self.is_condition_met(b),
+            Not(ref a) => !self.is_condition_met(a),
+        }
+    }
+
+    fn apply_program_line(&mut self, line: &ProgramLine) -> Result<Option<i64>, ExecutionError> {
         use Instruction::*;
 
-        let condition_met = match line.condition {
-            LessThan(ref name, amount) => self.get_register_value(name) < amount,
-            LessThanEqual(ref name, amount) => self.get_register_value(name) <= amount,
-            GreaterThan(ref name, amount) => self.get_register_value(name) > amount,
-            GreaterThanEqual(ref name, amount) => self.get_register_value(name) >= amount,
-            EqualTo(ref name, amount) => self.get_register_value(name) == amount,
-            NotEqualTo(ref name, amount) => self.get_register_value(name) != amount,
+        if !self.is_condition_met(&line.condition) {
+            return Ok(None);
+        }
+
+        let value = match line.instruction {
+            Increment(ref name, ref operand) => self.update_register(name, operand, |r, x| Some(r.wrapping_add(x)))?,
+            Decrement(ref name, ref operand) => self.update_register(name, operand, |r, x| Some(r.wrapping_sub(x)))?,
+            Multiply(ref name, ref operand) => self.update_register(name, operand, |r, x| Some(r.wrapping_mul(x)))?,
+            Divide(ref name, ref operand) => self.update_register(name, operand, |r, x| if x == 0 { None } else { Some(r.wrapping_div(x)) })?,
+            Modulo(ref name, ref operand) => self.update_register(name, operand, |r, x| if x == 0 { None } else { Some(r.wrapping_rem(x)) })?,
+            Set(ref name, ref operand) => self.update_register(name, operand, |_, x| Some(x))?,
         };
+        Ok(Some(value))
+    }
+}
 
-        if condition_met {
-            match line.instruction {
-                Increment(ref name, amount) => Some(self.increase_register_value(name, amount)),
-                Decrement(ref name, amount) => Some(self.decrease_register_value(name, amount)),
-            }
-        } else {
-            None
-        }
+fn parse_operand(operand_str: &str) -> Result<Operand, String> {
+    if let Ok(value) = operand_str.parse() {
+        Ok(Operand::Constant(value))
+    } else if !operand_str.is_empty() && operand_str.chars().all(|c| c.is_ascii_lowercase()) {
+        Ok(Operand::Register(operand_str.to_string()))
+    } else {
+        Err(format!("{} is neither a register nor a number", operand_str))
     }
 }
 
-fn parse_instruction(instruction_str: &str) -> Instruction {
+fn parse_instruction(instruction_str: &str) -> Result<Instruction, String> {
     lazy_static! {
-        static ref INSTRUCTION_REGEX: Regex = Regex::new("([a-z]+) (inc|dec) (-?[0-9]+)").unwrap();
+        static ref INSTRUCTION_REGEX: Regex = Regex::new("^([a-z]+) ([a-z]+) ([^ ]+)$").unwrap();
     }
 
-    let captures = INSTRUCTION_REGEX.captures(instruction_str).unwrap();
+    let captures = INSTRUCTION_REGEX.captures(instruction_str.trim())
+        .ok_or_else(|| format!("Malformed instruction {}", instruction_str))?;
     let name = captures[1].to_string();
-    let amount: i64 = captures[3].parse().unwrap();
+    let operand = parse_operand(&captures[3])?;
 
     match &captures[2] {
-        "inc" => Instruction::Increment(name, amount),
-        "dec" => Instruction::Decrement(name, amount),
-        x => panic!("Unrecognised instruction {}", x),
+        "inc" => Ok(Instruction::Increment(name, operand)),
+        "dec" => Ok(Instruction::Decrement(name, operand)),
+        "mul" => Ok(Instruction::Multiply(name, operand)),
+        "div" => Ok(Instruction::Divide(name, operand)),
+        "mod" => Ok(Instruction::Modulo(name, operand)),
+        "set" => Ok(Instruction::Set(name, operand)),
+        x => Err(format!("Unrecognised instruction {}", x)),
     }
 }
 
-fn parse_condition(condition_str: &str) -> Condition {
-    lazy_static! {
-        static ref CONDITION_REGEX: Regex = Regex::new("([a-z]+) (<|<=|>|>=|==|!=) (-?[0-9]+)").unwrap();
+// Conditions are comparisons combined with `not`, `and` and `or`, binding in
+// that order from tightest to loosest, with parentheses for grouping.
+struct ConditionParser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> ConditionParser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).cloned()
     }
 
-    let captures = CONDITION_REGEX.captures(condition_str).unwrap();
-    let name = captures[1].to_string();
-    let amount: i64 = captures[3].parse().unwrap();
+    fn next(&mut self) -> Result<&'a str, String> {
+        let token = self.peek().ok_or_else(|| "Condition ends unexpectedly".to_string())?;
+        self.position += 1;
+        Ok(token)
+    }
 
-    match &captures[2] {
-        "<" => Condition::LessThan(name, amount),
-        "<=" => Condition::LessThanEqual(name, amount),
-        ">" => Condition::GreaterThan(name, amount),
-        ">=" => Condition::GreaterThanEqual(name, amount),
-        "==" => Condition::EqualTo(name, amount),
-        "!=" => Condition::NotEqualTo(name, amount),
-        x => panic!("Unrecognised condition {}", x),
+    fn parse_or(&mut self) -> Result<Condition, String> {
+        let mut condition = self.parse_and()?;
+        while self.peek() == Some("or") {
+            self.position += 1;
+            condition = Condition::Or(Box::new(condition), Box::new(self.parse_and()?));
+        }
+        Ok(condition)
+    }
+
+    fn parse_and(&mut self) -> Result<Condition, String> {
+        let mut condition = self.parse_not()?;
+        while self.peek() == Some("and") {
+            self.position += 1;
+            condition = Condition::And(Box::new(condition), Box::new(self.parse_not()?));
+        }
+        Ok(condition)
+    }
+
+    fn parse_not(&mut self) -> Result<Condition, String> {
+        match self.peek() {
+            Some("not") => {
+                self.position += 1;
+                Ok(Condition::Not(Box::new(self.parse_not()?)))
+            },
+            Some("(") => {
+                self.position += 1;
+                let condition = self.parse_or()?;
+                match self.next()? {
+                    ")" => Ok(condition),
+                    x => Err(format!("Expected ) but found {}", x)),
+                }
+            },
+            _ => self.parse_comparison(),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Condition, String> {
+        let a = parse_operand(self.next()?)?;
+        let comparison = self.next()?;
+        let b = parse_operand(self.next()?)?;
+
+        match comparison {
+            "<" => Ok(Condition::LessThan(a, b)),
+            "<=" => Ok(Condition::LessThanEqual(a, b)),
+            ">" => Ok(Condition::GreaterThan(a, b)),
+            ">=" => Ok(Condition::GreaterThanEqual(a, b)),
+            "==" => Ok(Condition::EqualTo(a, b)),
+            "!=" => Ok(Condition::NotEqualTo(a, b)),
+            x => Err(format!("Unrecognised condition {}", x)),
+        }
+    }
+}
+
+fn parse_condition(condition_str: &str) -> Result<Condition, String> {
+    let spaced = condition_str.replace('(', " ( ").replace(')', " ) ");
+    let mut parser = ConditionParser { tokens: spaced.split_whitespace().collect(), position: 0 };
+    let condition = parser.parse_or()?;
+
+    match parser.peek() {
+        None => Ok(condition),
+        Some(x) => Err(format!("Unexpected {} in condition", x)),
     }
 }
 
-fn parse_program_line(line: &str) -> ProgramLine {
-    let parts: Vec<_> = line.split(" if ").collect();
-    ProgramLine {
-        instruction: parse_instruction(parts[0]),
-        condition: parse_condition(parts[1]),
+fn parse_program_line(line: &str) -> Result<ProgramLine, String> {
+    let parts: Vec<_> = line.splitn(2, " if ").collect();
+    if parts.len() != 2 {
+        return Err(format!("Missing condition in {}", line));
     }
+
+    Ok(ProgramLine {
+        instruction: parse_instruction(parts[0])?,
+        condition: parse_condition(parts[1])?,
+    })
 }
 
 fn read_program_lines_from_file(path: &str) -> Vec<String> {
@@ -136,34 +321,193 @@ fn read_program_lines_from_file(path: &str) -> Vec<String> {
     reader.lines().map(|line| line.unwrap()).collect()
 }
 
-fn part1() {
-    let mut cpu = Cpu::new();
-    let program = read_program_lines_from_file(INPUT_PATH);
-    for line in program {
-        let parsed_line = parse_program_line(&line);
-        cpu.apply_program_line(&parsed_line);
+fn parse_program(lines: &[String]) -> Vec<ProgramLine> {
+    lines.iter().enumerate().map(|(idx, line)| {
+        parse_program_line(line).unwrap_or_else(|e| panic!("Line {}: {}", idx + 1, e))
+    }).collect()
+}
+
+struct Options {
+    input_path: String,
+    benchmark_lines: Option<usize>,
+    lines_csv_path: Option<String>,
+    registers_csv_path: Option<String>,
+    history_json_path: Option<String>,
+    analyse: bool,
+}
+
+fn options_from_args() -> Options {
+    let mut options = Options {
+        input_path: INPUT_PATH.to_string(),
+        benchmark_lines: None,
+        lines_csv_path: None,
+        registers_csv_path: None,
+        history_json_path: None,
+        analyse: false,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => options.input_path = args.next().expect("--input expects a path"),
+            "--benchmark" => options.benchmark_lines = Some(args.next().and_then(|n| n.parse().ok()).expect("--benchmark expects a line count")),
+            "--lines-csv" => options.lines_csv_path = Some(args.next().expect("--lines-csv expects a path")),
+            "--registers-csv" => options.registers_csv_path = Some(args.next().expect("--registers-csv expects a path")),
+            "--history-json" => options.history_json_path = Some(args.next().expect("--history-json expects a path")),
+            "--analyse" => options.analyse = true,
+            _ => panic!("Unrecognised argument {}", arg),
+        }
     }
+    options
+}
 
-    println!("The answer to Part 1 is {}", cpu.get_max_register_value());
+fn part1(path: &str) {
+    let program = parse_program(&read_program_lines_from_file(path));
+    let bytecode = bytecode::compile(&program);
+    let mut machine = Machine::new(&bytecode);
+    machine.run(&bytecode, |_, _| {}).unwrap_or_else(|e| panic!("{}", e));
+
+    println!("The answer to Part 1 is {}", machine.get_max_register_value().unwrap());
 }
 
-fn part2() {
-    let mut cpu = Cpu::new();
-    let program = read_program_lines_from_file(INPUT_PATH);
+fn part2(path: &str) {
+    let program = parse_program(&read_program_lines_from_file(path));
+    let bytecode = bytecode::compile(&program);
+    let mut machine = Machine::new(&bytecode);
     let mut max_register_value = i64::min_value();
+    machine.run(&bytecode, |_, x| max_register_value = cmp::max(max_register_value, x)).unwrap_or_else(|e| panic!("{}", e));
 
-    for line in program {
-        let parsed_line = parse_program_line(&line);
-        match cpu.apply_program_line(&parsed_line) {
-            Some(x) => max_register_value = cmp::max(max_register_value, x),
-            None => {},
-        }
+    println!("The answer to Part 2 is {}", max_register_value);
+}
+
+fn record(options: &Options) {
+    let program = parse_program(&read_program_lines_from_file(&options.input_path));
+    let recorder = recorder::record_program(&program).unwrap_or_else(|e| panic!("{}", e));
+
+    println!("{} lines ran and {} were skipped, touching {} registers",
+             recorder.get_executed_count(), recorder.get_skipped_count(), recorder.get_registers().len());
+    if let Some(peak) = recorder.get_peak() {
+        println!("The peak value {} was first written to {} on line {}", peak.value, peak.register, peak.line);
     }
 
-    println!("The answer to Part 2 is {}", max_register_value);
+    let create = |path: &str| BufWriter::new(File::create(path).expect("Unable to create file"));
+    if let Some(ref path) = options.lines_csv_path {
+        recorder.write_lines_csv(&mut create(path)).expect("Unable to write CSV");
+    }
+    if let Some(ref path) = options.registers_csv_path {
+        recorder.write_registers_csv(&mut create(path)).expect("Unable to write CSV");
+    }
+    if let Some(ref path) = options.history_json_path {
+        recorder.write_json(&mut create(path)).expect("Unable to write JSON");
+    }
+}
+
+fn analyse(path: &str) {
+    let program = parse_program(&read_program_lines_from_file(path));
+    let analysis = analysis::analyse(&program);
+    let join = |names: Vec<&str>| if names.is_empty() { "none".to_string() } else { names.join(", ") };
+
+    println!("Register dependencies:");
+    for (source, targets) in &analysis.influences {
+        println!("  {} influences {}", source, join(targets.iter().map(|name| name.as_str()).collect()));
+    }
+    println!("Dead registers: {}", join(analysis.dead_registers.iter().map(|name| name.as_str()).collect()));
+    println!("Registers never written: {}", join(analysis.unwritten_registers.iter().map(|name| name.as_str()).collect()));
+    let lines: Vec<String> = analysis.never_true_lines.iter().map(|line| line.to_string()).collect();
+    println!("Lines whose condition is always false: {}", join(lines.iter().map(|line| line.as_str()).collect()));
 }
 
 fn main() {
-    part1();
-    part2();
+    let options = options_from_args();
+    part1(&options.input_path);
+    part2(&options.input_path);
+    if let Some(line_count) = options.benchmark_lines {
+        benchmark::run(line_count);
+    }
+    if options.lines_csv_path.is_some() || options.registers_csv_path.is_some() || options.history_json_path.is_some() {
+        record(&options);
+    }
+    if options.analyse {
+        analyse(&options.input_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holds(condition: &str, registers: &[(&str, i64)]) -> bool {
+        let condition = parse_condition(condition).unwrap_or_else(|e| panic!("{}: {}", condition, e));
+        let mut cpu = Cpu::new();
+        for &(name, value) in registers {
+            *cpu.get_register_mut(name) = value;
+        }
+        cpu.is_condition_met(&condition)
+    }
+
+    fn parse_error(condition: &str) -> String {
+        match parse_condition(condition) {
+            Ok(_) => panic!("{} parsed", condition),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn parses_comparisons_of_registers_and_constants() {
+        assert!(holds("a > 1", &[("a", 2)]));
+        assert!(!holds("a > 1", &[("a", 1)]));
+        assert!(holds("a <= b", &[("a", 1), ("b", 1)]));
+        assert!(holds("-3 < a", &[]));
+        assert!(holds("a == b", &[]));
+        assert!(holds("5 != 4", &[]));
+        assert!(holds("x >= -1", &[("x", -1)]));
+    }
+
+    #[test]
+    fn binds_not_and_and_or_from_tightest_to_loosest() {
+        let registers = [("a", 1), ("b", 0), ("c", 0)];
+        assert!(holds("a > 0 or b > 0 and c > 0", &registers));
+        assert!(!holds("(a > 0 or b > 0) and c > 0", &registers));
+        assert!(!holds("not a > 0 or b > 0", &registers));
+        assert!(holds("not (a > 0 and b > 0)", &registers));
+        assert!(holds("not not a > 0", &registers));
+        assert!(holds("((a > 0))and(b == 0)", &registers));
+    }
+
+    #[test]
+    fn collects_registers_from_every_comparison() {
+        let condition = parse_condition("a > 1 and not (b == c or 2 < d)").unwrap();
+        assert_eq!(condition.get_registers(), vec!["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn reports_malformed_conditions() {
+        assert_eq!(parse_error("a >"), "Condition ends unexpectedly");
+        assert_eq!(parse_error("a => 1"), "Unrecognised condition =>");
+        assert_eq!(parse_error("A > 1"), "A is neither a register nor a number");
+        assert_eq!(parse_error("(a > 1"), "Condition ends unexpectedly");
+        assert_eq!(parse_error("(a > 1 b"), "Expected ) but found b");
+        assert_eq!(parse_error("a > 1)"), "Unexpected ) in condition");
+        assert_eq!(parse_error("a > 1 xor b > 1"), "Unexpected xor in condition");
+    }
+
+    #[test]
+    fn runs_program_lines() {
+        let lines: Vec<String> = ["b inc 5 if a > 1", "a inc 1 if b < 5", "c dec -10 if a >= 1", "c inc -20 if c == 10",
+                                  "d set c if not c == 0", "d mul 3 if d != 0 and a == 1", "d div a if d < 0 or b > 0"]
+            .iter().map(|line| line.to_string()).collect();
+        let mut cpu = Cpu::new();
+        let results: Vec<_> = parse_program(&lines).iter().map(|line| cpu.apply_program_line(line).unwrap()).collect();
+        assert_eq!(results, vec![None, Some(1), Some(10), Some(-10), Some(-10), Some(-30), Some(-30)]);
+
+        let line = parse_program_line("a mod b if a == 1").unwrap();
+        assert_eq!(cpu.apply_program_line(&line), Err(ExecutionError::DivisionByZero("a".to_string())));
+        assert_eq!(cpu.get_max_register_value(), 1);
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        assert_eq!(parse_program_line("a inc 1").err().unwrap(), "Missing condition in a inc 1");
+        assert_eq!(parse_program_line("a add 1 if a > 0").err().unwrap(), "Unrecognised instruction add");
+        assert_eq!(parse_program_line("a inc if a > 0").err().unwrap(), "Malformed instruction a inc");
+    }
 }
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use {Cpu, ExecutionError, ProgramLine};
+
+// Line numbers count from 1, as in the program file.
+pub struct RegisterHistory {
+    pub first_line: usize,
+    pub values: Vec<(usize, i64)>,
+}
+
+impl RegisterHistory {
+    pub fn get_writes(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn get_min(&self) -> Option<i64> {
+        self.values.iter().map(|&(_, value)| value).min()
+    }
+
+    pub fn get_max(&self) -> Option<i64> {
+        self.values.iter().map(|&(_, value)| value).max()
+    }
+}
+
+pub struct Peak<'a> {
+    pub register: &'a str,
+    pub value: i64,
+    pub line: usize,
+}
+
+pub struct Recorder {
+    registers: BTreeMap<String, RegisterHistory>,
+    // For each line, the register it updated and the new value, or `None`
+    // when its condition was false and the line was skipped.
+    lines: Vec<Option<(String, i64)>>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder { registers: BTreeMap::new(), lines: Vec::new() }
+    }
+
+    // A register is touched by the first line which mentions it, whether that
+    // line reads it, writes it or is skipped.
+    pub fn record(&mut self, line: &ProgramLine, outcome: Option<i64>) {
+        let line_number = self.lines.len() + 1;
+
+        let name = line.instruction.get_register();
+        let mut mentioned = vec![name];
+        mentioned.extend(line.instruction.get_operand().get_register());
+        mentioned.extend(line.condition.get_registers());
+        for name in mentioned {
+            if !self.registers.contains_key(name) {
+                self.registers.insert(name.to_string(), RegisterHistory { first_line: line_number, values: Vec::new() });
+            }
+        }
+
+        if let Some(value) = outcome {
+            self.registers.get_mut(name).unwrap().values.push((line_number, value));
+        }
+        self.lines.push(outcome.map(|value| (name.to_string(), value)));
+    }
+
+    pub fn get_registers(&self) -> &BTreeMap<String, RegisterHistory> {
+        &self.registers
+    }
+
+    pub fn get_executed_count(&self) -> usize {
+        self.lines.iter().filter(|line| line.is_some()).count()
+    }
+
+    pub fn get_skipped_count(&self) -> usize {
+        self.lines.len() - self.get_executed_count()
+    }
+
+    // The highest value ever written, and the first time it was written.
+    pub fn get_peak(&self) -> Option<Peak<'_>> {
+        let mut peak = None;
+        for (idx, line) in self.lines.iter().enumerate() {
+            if let Some((ref name, value)) = *line {
+                match peak {
+                    Some(Peak { value: best, .. }) if best >= value => {},
+                    _ => peak = Some(Peak { register: name, value, line: idx + 1 }),
+                }
+            }
+        }
+        peak
+    }
+
+    pub fn write_lines_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "line,condition,register,value")?;
+        for (idx, line) in self.lines.iter().enumerate() {
+            match *line {
+                Some((ref name, value)) => writeln!(writer, "{},true,{},{}", idx + 1, name, value)?,
+                None => writeln!(writer, "{},false,,", idx + 1)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_registers_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "register,first_line,writes,min,max")?;
+        for (name, history) in &self.registers {
+            let show = |value: Option<i64>| value.map(|v| v.to_string()).unwrap_or_default();
+            writeln!(writer, "{},{},{},{},{}", name, history.first_line, history.get_writes(),
+                     show(history.get_min()), show(history.get_max()))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let show = |value: Option<i64>| value.map(|v| v.to_string()).unwrap_or_else(|| "null".to_string());
+
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"registers\": {{")?;
+        for (idx, (name, history)) in self.registers.iter().enumerate() {
+            let values: Vec<String> = history.values.iter()
+                .map(|&(line, value)| format!("{{\"line\": {}, \"value\": {}}}", line, value))
+                .collect();
+            writeln!(writer, "    \"{}\": {{", name)?;
+            writeln!(writer, "      \"first_line\": {},", history.first_line)?;
+            writeln!(writer, "      \"writes\": {},", history.get_writes())?;
+            writeln!(writer, "      \"min\": {},", show(history.get_min()))?;
+            writeln!(writer, "      \"max\": {},", show(history.get_max()))?;
+            writeln!(writer, "      \"history\": [{}]", values.join(", "))?;
+            writeln!(writer, "    }}{}", if idx + 1 < self.registers.len() { "," } else { "" })?;
+        }
+        writeln!(writer, "  }},")?;
+
+        let executed: Vec<String> = self.lines.iter().enumerate()
+            .filter(|&(_, line)| line.is_some()).map(|(idx, _)| (idx + 1).to_string()).collect();
+        let skipped: Vec<String> = self.lines.iter().enumerate()
+            .filter(|&(_, line)| line.is_none()).map(|(idx, _)| (idx + 1).to_string()).collect();
+        writeln!(writer, "  \"executed_lines\": [{}],", executed.join(", "))?;
+        writeln!(writer, "  \"skipped_lines\": [{}],", skipped.join(", "))?;
+
+        match self.get_peak() {
+            Some(peak) => writeln!(writer, "  \"peak\": {{\"register\": \"{}\", \"value\": {}, \"line\": {}}}", peak.register, peak.value, peak.line)?,
+            None => writeln!(writer, "  \"peak\": null")?,
+        }
+        writeln!(writer, "}}")
+    }
+}
+
+pub fn record_program(program: &[ProgramLine]) -> Result<Recorder, ExecutionError> {
+    let mut cpu = Cpu::new();
+    let mut recorder = Recorder::new();
+    for line in program {
+        let outcome = cpu.apply_program_line(line)?;
+        recorder.record(line, outcome);
+    }
+    Ok(recorder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_program;
+
+    fn example() -> Recorder {
+        let lines: Vec<String> = ["b inc 5 if a > 1", "a inc 1 if b < 5", "c dec -10 if a >= 1", "c inc -20 if c == 10",
+                                  "d set c if e < 1", "a inc 9 if a > 0"]
+            .iter().map(|line| line.to_string()).collect();
+        record_program(&parse_program(&lines)).unwrap()
+    }
+
+    fn output<F: Fn(&Recorder, &mut Vec<u8>) -> io::Result<()>>(write: F) -> String {
+        let mut output = Vec::new();
+        write(&example(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn notes_where_each_register_first_appears() {
+        let recorder = example();
+        let first_lines: Vec<(&str, usize)> = recorder.get_registers().iter()
+            .map(|(name, history)| (name.as_str(), history.first_line))
+            .collect();
+        // `a` and `b` first appear on a skipped line, and `e` is only ever read.
+        assert_eq!(first_lines, vec![("a", 1), ("b", 1), ("c", 3), ("d", 5), ("e", 5)]);
+        assert_eq!(recorder.get_executed_count(), 5);
+        assert_eq!(recorder.get_skipped_count(), 1);
+    }
+
+    #[test]
+    fn counts_writes_and_their_range() {
+        let recorder = example();
+        let summary: Vec<(&str, usize, Option<i64>, Option<i64>)> = recorder.get_registers().iter()
+            .map(|(name, history)| (name.as_str(), history.get_writes(), history.get_min(), history.get_max()))
+            .collect();
+        assert_eq!(summary, vec![("a", 2, Some(1), Some(10)), ("b", 0, None, None), ("c", 2, Some(-10), Some(10)),
+                                 ("d", 1, Some(-10), Some(-10)), ("e", 0, None, None)]);
+    }
+
+    #[test]
+    fn finds_the_first_line_to_reach_the_peak() {
+        let recorder = example();
+        let peak = recorder.get_peak().unwrap();
+        assert_eq!((peak.register, peak.value, peak.line), ("c", 10, 3));
+        assert!(Recorder::new().get_peak().is_none());
+    }
+
+    #[test]
+    fn writes_csv() {
+        assert_eq!(output(|recorder, writer| recorder.write_lines_csv(writer)),
+                   "line,condition,register,value\n1,false,,\n2,true,a,1\n3,true,c,10\n4,true,c,-10\n5,true,d,-10\n6,true,a,10\n");
+        assert_eq!(output(|recorder, writer| recorder.write_registers_csv(writer)),
+                   "register,first_line,writes,min,max\na,1,2,1,10\nb,1,0,,\nc,3,2,-10,10\nd,5,1,-10,-10\ne,5,0,,\n");
+    }
+
+    #[test]
+    fn writes_json() {
+        assert_eq!(output(|recorder, writer| recorder.write_json(writer)), r#"{
+  "registers": {
+    "a": {
+      "first_line": 1,
+      "writes": 2,
+      "min": 1,
+      "max": 10,
+      "history": [{"line": 2, "value": 1}, {"line": 6, "value": 10}]
+    },
+    "b": {
+      "first_line": 1,
+      "writes": 0,
+      "min": null,
+      "max": null,
+      "history": []
+    },
+    "c": {
+      "first_line": 3,
+      "writes": 2,
+      "min": -10,
+      "max": 10,
+      "history": [{"line": 3, "value": 10}, {"line": 4, "value": -10}]
+    },
+    "d": {
+      "first_line": 5,
+      "writes": 1,
+      "min": -10,
+      "max": -10,
+      "history": [{"line": 5, "value": -10}]
+    },
+    "e": {
+      "first_line": 5,
+      "writes": 0,
+      "min": null,
+      "max": null,
+      "history": []
+    }
+  },
+  "executed_lines": [2, 3, 4, 5, 6],
+  "skipped_lines": [1],
+  "peak": {"register": "c", "value": 10, "line": 3}
+}
+"#);
+    }
+}
@@ -0,0 +1,155 @@
+use problem::Problem;
+use processor::{Mode, StreamProcessor};
+use token::{self, Token};
+use StringStats;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    // Each child on its own line, indented two spaces per level.
+    Pretty,
+    // Cancelled characters and empty garbage removed, with no whitespace.
+    Canonical,
+    // Groups only, with no whitespace.
+    Minified,
+}
+
+struct Writer {
+    style: Style,
+    output: String,
+    // Whether each open group, and the top level below them, has had a child
+    // written yet.
+    has_child: Vec<bool>,
+}
+
+impl Writer {
+    fn begin_child(&mut self) {
+        let depth = self.has_child.len() - 1;
+        let has_child = self.has_child.last_mut().unwrap();
+        if *has_child && depth > 0 {
+            self.output.push(',');
+        }
+        if self.style == Style::Pretty && (*has_child || depth > 0) {
+            self.output.push('\n');
+            for _ in 0..depth {
+                self.output.push_str("  ");
+            }
+        }
+        *has_child = true;
+    }
+
+    fn open_group(&mut self) {
+        self.begin_child();
+        self.output.push('{');
+        self.has_child.push(false);
+    }
+
+    fn close_group(&mut self) {
+        let had_children = self.has_child.pop().unwrap();
+        if self.style == Style::Pretty && had_children {
+            self.output.push('\n');
+            for _ in 1..self.has_child.len() {
+                self.output.push_str("  ");
+            }
+        }
+        self.output.push('}');
+    }
+
+    // `raw` is the garbage with its angle brackets.
+    fn garbage(&mut self, raw: &str) {
+        let text = match self.style {
+            Style::Pretty => raw.to_string(),
+            Style::Canonical => {
+                let mut chars = raw.chars();
+                let mut text = String::new();
+                while let Some(c) = chars.next() {
+                    if c == '!' {
+                        chars.next();
+                    } else {
+                        text.push(c);
+                    }
+                }
+                text
+            },
+            Style::Minified => return,
+        };
+
+        if text != "<>" || self.style == Style::Pretty {
+            self.begin_child();
+            self.output.push_str(&text);
+        }
+    }
+}
+
+fn check(input: &str) -> Result<StringStats, Vec<Problem>> {
+    let mut processor = StreamProcessor::new(Mode::Strict);
+    processor.feed(input.as_bytes());
+    processor.finish()
+}
+
+// Rewrites a well-formed stream, keeping the same groups in the same order.
+// Separators are regenerated and stray whitespace is dropped, so the score is
+// unchanged, as is the garbage total unless the garbage is minified away.
+pub fn format(input: &str, style: Style) -> Result<String, Vec<Problem>> {
+    check(input)?;
+
+    let mut writer = Writer { style, output: String::new(), has_child: vec![false] };
+    let mut garbage_start = 0;
+    for (token, offset) in token::tokenize(input) {
+        match token {
+            Token::GroupStart => writer.open_group(),
+            Token::GroupEnd => writer.close_group(),
+            Token::GarbageStart => garbage_start = offset,
+            Token::GarbageEnd => writer.garbage(&input[garbage_start..offset + 1]),
+            _ => {},
+        }
+    }
+    if style == Style::Pretty {
+        writer.output.push('\n');
+    }
+    Ok(writer.output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STYLES: [Style; 3] = [Style::Pretty, Style::Canonical, Style::Minified];
+
+    // Formatting must keep the score, keep the garbage total unless the
+    // garbage is minified away, and give the same output when run again.
+    fn check_preserved(input: &str) {
+        let before = check(input).unwrap();
+        for &style in &STYLES {
+            let output = format(input, style).unwrap();
+            let after = check(&output).unwrap_or_else(|_| panic!("{:?} output of {:?} is malformed: {:?}", style, input, output));
+            assert_eq!(after.score, before.score, "{:?} score of {:?}", style, input);
+            let expected_garbage = if style == Style::Minified { 0 } else { before.garbage_chars };
+            assert_eq!(after.garbage_chars, expected_garbage, "{:?} garbage of {:?}", style, input);
+            assert_eq!(format(&output, style).unwrap(), output, "Formatting {:?} twice as {:?}", input, style);
+        }
+    }
+
+    #[test]
+    fn preserves_the_meaning_of_the_stream() {
+        let inputs = ["{}", "{{<a!>b>,{}},{<>},{<!!>}}", "{<!>},<a>}", "{<>}", "{<>,<>,{<>}}", "{<{!>}>, {<!!!>>}}",
+                      "  {\n  {<é!é>},\n  {}\n}\n", "{{{}}}"];
+        for input in &inputs {
+            check_preserved(input);
+        }
+    }
+
+    #[test]
+    fn writes_each_style() {
+        let input = "{{<a!>b>,{}},{<>},{<!!>}}";
+        assert_eq!(format(input, Style::Pretty).unwrap(), "{\n  {\n    <a!>b>,\n    {}\n  },\n  {\n    <>\n  },\n  {\n    <!!>\n  }\n}\n");
+        assert_eq!(format(input, Style::Canonical).unwrap(), "{{<ab>,{}},{},{}}");
+        assert_eq!(format(input, Style::Minified).unwrap(), "{{{}},{},{}}");
+        assert_eq!(format("{<!>},<a>}", Style::Canonical).unwrap(), "{<},<a>}");
+    }
+
+    #[test]
+    fn refuses_malformed_streams() {
+        assert!(format("{<a>", Style::Pretty).is_err());
+        assert!(format("{}}", Style::Minified).is_err());
+    }
+}
@@ -1,5 +1,16 @@
+mod format;
+mod problem;
+mod processor;
+mod token;
+mod tree;
+
+use std::env;
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
+use std::process;
+use problem::Problem;
+use format::Style;
+use processor::{Mode, StreamProcessor};
 
 const INPUT_PATH: &'static str = "inputs/input.txt";
 
@@ -24,49 +35,142 @@ fn read_file_to_string(path: &str) -> String {
     result
 }
 
-fn stats(input: &str) -> StringStats {
-    let mut depth = 0;
-    let mut garbage = false;
-    let mut ignore = false;
-
-    let mut stats = StringStats::new();
-
-    for c in input.chars() {
-        if ignore {
-            ignore = false;
-        } else if garbage {
-            match c {
-                '>' => garbage = false,
-                '!' => ignore = true,
-                _ => stats.garbage_chars += 1,
-            }
-        } else {
-            match c {
-                '{' => {
-                    depth += 1;
-                    stats.score += depth;
-                },
-                '}' => depth -= 1,
-                '<' => garbage = true,
-                _ => {},
-            }
+fn stats(input: &str, mode: Mode) -> Result<StringStats, Vec<Problem>> {
+    let mut processor = StreamProcessor::new(mode);
+    processor.feed(input.as_bytes());
+    processor.finish()
+}
+
+fn report_problems(path: &str, problems: &[Problem]) -> ! {
+    eprintln!("The stream in {} is malformed:", path);
+    for problem in problems {
+        eprintln!("  {}", problem);
+    }
+    process::exit(1);
+}
+
+struct Options {
+    input_path: String,
+    show_tree: bool,
+    group_offset: Option<usize>,
+    stream_path: Option<String>,
+    progress: bool,
+    mode: Mode,
+    format_style: Option<Style>,
+    output_path: String,
+}
+
+fn options_from_args() -> Options {
+    let mut options = Options { input_path: INPUT_PATH.to_string(), show_tree: false, group_offset: None, stream_path: None, progress: false, mode: Mode::Lenient, format_style: None, output_path: "-".to_string() };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => options.input_path = args.next().expect("--input expects a path"),
+            "--tree" => options.show_tree = true,
+            "--group-at" => options.group_offset = Some(args.next().and_then(|n| n.parse().ok()).expect("--group-at expects a byte offset")),
+            "--stream" => options.stream_path = Some(args.next().expect("--stream expects a path, or - for stdin")),
+            "--progress" => options.progress = true,
+            "--strict" => options.mode = Mode::Strict,
+            "--format" => options.format_style = match args.next().as_deref() {
+                Some("pretty") => Some(Style::Pretty),
+                Some("canonical") => Some(Style::Canonical),
+                Some("minify") => Some(Style::Minified),
+                _ => panic!("--format expects pretty, canonical or minify"),
+            },
+            "--output" => options.output_path = args.next().expect("--output expects a path, or - for stdout"),
+            _ => panic!("Unrecognised argument {}", arg),
+        }
+    }
+    options
+}
+
+fn part1(options: &Options) {
+    let input = read_file_to_string(&options.input_path);
+    let stats = stats(&input, options.mode).unwrap_or_else(|problems| report_problems(&options.input_path, &problems));
+    println!("The answer to Part 1 is {}", stats.score);
+}
+
+fn part2(options: &Options) {
+    let input = read_file_to_string(&options.input_path);
+    let stats = stats(&input, options.mode).unwrap_or_else(|problems| report_problems(&options.input_path, &problems));
+    println!("The answer to Part 2 is {}", stats.garbage_chars);
+}
+
+fn describe_tree(options: &Options) {
+    let input = read_file_to_string(&options.input_path);
+    let stream = tree::parse(&input).unwrap_or_else(|problem| report_problems(&options.input_path, &[problem]));
+
+    println!("The tree has score {} and {} garbage characters", stream.get_score(), stream.get_garbage_chars());
+    if let Some((group, depth)) = stream.get_deepest_group() {
+        println!("The deepest group is at depth {}, spanning bytes {}..{}", depth, group.span.start, group.span.end);
+    }
+    if let Some(garbage) = stream.get_largest_garbage() {
+        println!("The largest garbage has {} characters, spanning bytes {}..{}", garbage.get_char_count(), garbage.span.start, garbage.span.end);
+    }
+    if let Some(offset) = options.group_offset {
+        match stream.get_group_at(offset) {
+            Some((group, depth)) => println!("Byte {} is in a group at depth {} with {} children, spanning bytes {}..{}",
+                                             offset, depth, group.children.len(), group.span.start, group.span.end),
+            None => println!("Byte {} is not in any group", offset),
         }
     }
+}
+
+const CHUNK_SIZE: usize = 1 << 16;
 
-    stats
+fn process_stream<R: Read>(mut reader: R, options: &Options) -> io::Result<Result<StringStats, Vec<Problem>>> {
+    let mut processor = StreamProcessor::new(options.mode);
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut total = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(processor.finish()),
+            Ok(read) => read,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        total += read;
+        processor.feed(&buffer[..read]);
+        if options.progress {
+            let (stats, problems) = (processor.get_stats(), processor.get_problems().len());
+            eprintln!("After {} bytes: score {}, {} garbage characters, {} problems", total, stats.score, stats.garbage_chars, problems);
+        }
+    }
 }
 
-fn part1() {
-    let input = read_file_to_string(INPUT_PATH);
-    println!("The answer to Part 1 is {}", stats(&input).score);
+fn stream(path: &str, options: &Options) {
+    let stats = if path == "-" {
+        let stdin = io::stdin();
+        process_stream(stdin.lock(), options)
+    } else {
+        process_stream(File::open(path).expect("Unable to open file"), options)
+    }.expect("Unable to read stream").unwrap_or_else(|problems| report_problems(path, &problems));
+    println!("The stream has score {} and {} garbage characters", stats.score, stats.garbage_chars);
 }
 
-fn part2() {
-    let input = read_file_to_string(INPUT_PATH);
-    println!("The answer to Part 2 is {}", stats(&input).garbage_chars);
+fn format_input(style: Style, options: &Options) {
+    let input = read_file_to_string(&options.input_path);
+    let output = format::format(&input, style).unwrap_or_else(|problems| report_problems(&options.input_path, &problems));
+
+    if options.output_path == "-" {
+        print!("{}", output);
+    } else {
+        let mut file = File::create(&options.output_path).expect("Unable to create file");
+        file.write_all(output.as_bytes()).expect("Unable to write file");
+    }
 }
 
 fn main() {
-    part1();
-    part2();
+    let options = options_from_args();
+    part1(&options);
+    part2(&options);
+    if options.show_tree || options.group_offset.is_some() {
+        describe_tree(&options);
+    }
+    if let Some(ref path) = options.stream_path {
+        stream(path, &options);
+    }
+    if let Some(style) = options.format_style {
+        format_input(style, &options);
+    }
 }
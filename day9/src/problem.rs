@@ -0,0 +1,105 @@
+use std::fmt;
+
+use token::Token;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProblemKind {
+    UnmatchedCloser,
+    UnclosedGroup,
+    UnterminatedGarbage,
+    // The first byte of a character outside any group, other than whitespace
+    // around the outermost group.
+    StrayCharacter(u8),
+}
+
+// `offset` is the byte where the problem starts: the `}`, `{` or `<`
+// concerned, or the stray character. `depth` counts the groups open around
+// that byte, the innermost of which opened at `enclosing_group`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    pub offset: usize,
+    pub depth: usize,
+    pub enclosing_group: Option<usize>,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ProblemKind::UnmatchedCloser => write!(f, "Unmatched }} at byte {}", self.offset)?,
+            ProblemKind::UnclosedGroup => write!(f, "Group opened at byte {} is never closed", self.offset)?,
+            ProblemKind::UnterminatedGarbage => write!(f, "Garbage starting at byte {} is never terminated", self.offset)?,
+            ProblemKind::StrayCharacter(byte) if byte.is_ascii_graphic() => write!(f, "Stray '{}' at byte {}", byte as char, self.offset)?,
+            ProblemKind::StrayCharacter(byte) => write!(f, "Stray byte 0x{:02x} at byte {}", byte, self.offset)?,
+        }
+        match self.enclosing_group {
+            Some(start) => write!(f, " (depth {}, inside the group opened at byte {})", self.depth, start),
+            None => write!(f, " (outside any group)"),
+        }
+    }
+}
+
+// Follows the structure of a stream token by token, collecting the problems
+// that strict mode reports.
+pub struct Checker {
+    open_groups: Vec<usize>,
+    garbage_start: Option<usize>,
+    seen_group: bool,
+    problems: Vec<Problem>,
+}
+
+impl Checker {
+    pub fn new() -> Checker {
+        Checker { open_groups: Vec::new(), garbage_start: None, seen_group: false, problems: Vec::new() }
+    }
+
+    fn report(&mut self, kind: ProblemKind, offset: usize) {
+        let depth = self.open_groups.len();
+        let enclosing_group = self.open_groups.last().cloned();
+        self.problems.push(Problem { kind, offset, depth, enclosing_group });
+    }
+
+    // Each item outside the outermost group is reported once, at its first
+    // character, and is otherwise followed as normal so that its contents are
+    // not reported too.
+    pub fn check(&mut self, token: Token, offset: usize, byte: u8) {
+        let outside = self.open_groups.is_empty();
+        match token {
+            Token::GroupStart => {
+                if outside && self.seen_group {
+                    self.report(ProblemKind::StrayCharacter(byte), offset);
+                }
+                self.seen_group = true;
+                self.open_groups.push(offset);
+            },
+            Token::GroupEnd => match self.open_groups.pop() {
+                Some(_) => {},
+                None => self.report(ProblemKind::UnmatchedCloser, offset),
+            },
+            Token::GarbageStart => {
+                if outside {
+                    self.report(ProblemKind::StrayCharacter(byte), offset);
+                }
+                self.garbage_start = Some(offset);
+            },
+            Token::GarbageEnd => self.garbage_start = None,
+            Token::Separator if outside => self.report(ProblemKind::StrayCharacter(byte), offset),
+            Token::Stray if outside && !byte.is_ascii_whitespace() => self.report(ProblemKind::StrayCharacter(byte), offset),
+            _ => {},
+        }
+    }
+
+    pub fn get_problems(&self) -> &[Problem] {
+        &self.problems
+    }
+
+    pub fn finish(mut self) -> Vec<Problem> {
+        if let Some(start) = self.garbage_start {
+            self.report(ProblemKind::UnterminatedGarbage, start);
+        }
+        while let Some(start) = self.open_groups.pop() {
+            self.report(ProblemKind::UnclosedGroup, start);
+        }
+        self.problems
+    }
+}
@@ -0,0 +1,106 @@
+use problem::{Checker, Problem};
+use token::{Token, Tokenizer};
+use StringStats;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    // Reports every structural problem in the stream.
+    Strict,
+    // Accepts anything, scoring it as best it can.
+    Lenient,
+}
+
+// Computes the same statistics as `stats`, but from input supplied in chunks
+// of any size. A chunk may end anywhere, even straight after a `!` or part way
+// through a UTF-8 character; the tokenizer carries that state to the next one.
+// An unmatched `}` takes the depth below zero, with the arithmetic wrapping so
+// that the groups which follow score as though the depth were negative.
+pub struct StreamProcessor {
+    tokenizer: Tokenizer,
+    depth: u64,
+    stats: StringStats,
+    checker: Option<Checker>,
+}
+
+impl StreamProcessor {
+    pub fn new(mode: Mode) -> StreamProcessor {
+        let checker = if mode == Mode::Strict { Some(Checker::new()) } else { None };
+        StreamProcessor { tokenizer: Tokenizer::new(), depth: 0, stats: StringStats::new(), checker }
+    }
+
+    // Returns the statistics for everything fed so far.
+    pub fn feed(&mut self, chunk: &[u8]) -> &StringStats {
+        for &byte in chunk {
+            let token = self.tokenizer.push(byte);
+            if let (Some(ref mut checker), Some((token, offset))) = (self.checker.as_mut(), token) {
+                checker.check(token, offset, byte);
+            }
+
+            match token {
+                Some((Token::GroupStart, _)) => {
+                    self.depth = self.depth.wrapping_add(1);
+                    self.stats.score = self.stats.score.wrapping_add(self.depth);
+                },
+                Some((Token::GroupEnd, _)) => self.depth = self.depth.wrapping_sub(1),
+                Some((Token::GarbageChar, _)) => self.stats.garbage_chars += 1,
+                _ => {},
+            }
+        }
+        &self.stats
+    }
+
+    pub fn get_stats(&self) -> &StringStats {
+        &self.stats
+    }
+
+    // The problems found so far, which is always none in lenient mode. Some
+    // problems only show up once the stream is finished.
+    pub fn get_problems(&self) -> &[Problem] {
+        self.checker.as_ref().map_or(&[], |checker| checker.get_problems())
+    }
+
+    pub fn finish(self) -> Result<StringStats, Vec<Problem>> {
+        let problems = self.checker.map_or_else(Vec::new, |checker| checker.finish());
+        if problems.is_empty() { Ok(self.stats) } else { Err(problems) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_chunks(chunks: &[&[u8]], mode: Mode) -> Result<(u64, usize), Vec<Problem>> {
+        let mut processor = StreamProcessor::new(mode);
+        for chunk in chunks {
+            processor.feed(chunk);
+        }
+        processor.finish().map(|stats| (stats.score, stats.garbage_chars))
+    }
+
+    #[test]
+    fn carries_a_cancel_across_chunks() {
+        assert_eq!(in_chunks(&[b"{<a!", b">b>}"], Mode::Strict), Ok((1, 2)));
+        assert_eq!(in_chunks(&[b"{<!", b"!", b">,{}}"], Mode::Strict), Ok((3, 0)));
+        assert_eq!(in_chunks(&[b"{<!!", b"!>>}"], Mode::Strict), Ok((1, 0)));
+        assert_eq!(in_chunks(&[b"{<!", b"", b"\xc3\xa9", b"\xc3", b"\xa9>}"], Mode::Strict), Ok((1, 1)));
+    }
+
+    // Splitting the input anywhere, or feeding it a byte at a time, must give
+    // the same result as feeding it whole.
+    #[test]
+    fn gives_the_same_result_however_the_input_is_split() {
+        let inputs: &[&str] = &["{{<a!>b>,{}},{<>},{<!!>}}", "{<!!!>>,{<é!é>}}", "{<{!}>}", "{<!>}", "}{<a>}}{", "x{<!"];
+        for input in inputs {
+            let bytes = input.as_bytes();
+            for &mode in &[Mode::Strict, Mode::Lenient] {
+                let whole = in_chunks(&[bytes], mode);
+                for split in 0..=bytes.len() {
+                    let (head, tail) = bytes.split_at(split);
+                    assert_eq!(in_chunks(&[head, tail], mode), whole, "Split {:?} at {}", input, split);
+                }
+                let bytewise: Vec<&[u8]> = bytes.chunks(1).collect();
+                assert_eq!(in_chunks(&bytewise, mode), whole, "{:?} a byte at a time", input);
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token {
+    GroupStart,
+    GroupEnd,
+    Separator,
+    GarbageStart,
+    GarbageEnd,
+    // A character inside garbage which counts towards the garbage total.
+    GarbageChar,
+    // A `!` together with the character it cancels.
+    Cancel,
+    // Anything else outside garbage.
+    Stray,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Normal,
+    Garbage,
+    // Inside garbage, just after a `!` at the given offset.
+    Cancelling(usize),
+}
+
+// Turns bytes into tokens one at a time, so that input can be fed in pieces.
+// UTF-8 continuation bytes never start a token: they belong to the character
+// before them, so garbage and cancellation work on whole characters.
+pub struct Tokenizer {
+    state: State,
+    offset: usize,
+}
+
+impl Tokenizer {
+    pub fn new() -> Tokenizer {
+        Tokenizer { state: State::Normal, offset: 0 }
+    }
+
+    // Returns the token the byte completes, if any, with its starting offset.
+    pub fn push(&mut self, byte: u8) -> Option<(Token, usize)> {
+        let offset = self.offset;
+        self.offset += 1;
+        if byte & 0xc0 == 0x80 {
+            return None;
+        }
+
+        match self.state {
+            State::Cancelling(start) => {
+                self.state = State::Garbage;
+                Some((Token::Cancel, start))
+            },
+            State::Garbage => match byte {
+                b'>' => {
+                    self.state = State::Normal;
+                    Some((Token::GarbageEnd, offset))
+                },
+                b'!' => {
+                    self.state = State::Cancelling(offset);
+                    None
+                },
+                _ => Some((Token::GarbageChar, offset)),
+            },
+            State::Normal => match byte {
+                b'{' => Some((Token::GroupStart, offset)),
+                b'}' => Some((Token::GroupEnd, offset)),
+                b',' => Some((Token::Separator, offset)),
+                b'<' => {
+                    self.state = State::Garbage;
+                    Some((Token::GarbageStart, offset))
+                },
+                _ => Some((Token::Stray, offset)),
+            },
+        }
+    }
+}
+
+pub struct Tokens<'a> {
+    bytes: &'a [u8],
+    tokenizer: Tokenizer,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (Token, usize);
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        while let Some((&byte, rest)) = self.bytes.split_first() {
+            self.bytes = rest;
+            if let Some(token) = self.tokenizer.push(byte) {
+                return Some(token);
+            }
+        }
+        None
+    }
+}
+
+pub fn tokenize(input: &str) -> Tokens<'_> {
+    Tokens { bytes: input.as_bytes(), tokenizer: Tokenizer::new() }
+}
@@ -0,0 +1,228 @@
+use problem::{Problem, ProblemKind};
+use token::{self, Token};
+
+// Byte offsets into the input, from `start` up to but not including `end`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+}
+
+#[derive(Debug)]
+pub struct Group {
+    pub children: Vec<Node>,
+    pub span: Span,
+}
+
+// Dropping nested groups one inside another would recurse once per level, so
+// the children are moved onto a stack instead and each group is dropped empty.
+impl Drop for Group {
+    fn drop(&mut self) {
+        let mut stack: Vec<Node> = self.children.drain(..).collect();
+        while let Some(node) = stack.pop() {
+            if let Node::Group(mut group) = node {
+                stack.append(&mut group.children);
+            }
+        }
+    }
+}
+
+// `raw` is the text between the angle brackets, exactly as it appears in the
+// input, and `span` includes the brackets.
+#[derive(Debug)]
+pub struct Garbage {
+    pub raw: String,
+    pub cancelled_chars: usize,
+    pub span: Span,
+}
+
+impl Garbage {
+    // The characters which count towards the garbage total: everything except
+    // each `!` and the character it cancels.
+    pub fn get_char_count(&self) -> usize {
+        self.raw.chars().count() - 2 * self.cancelled_chars
+    }
+}
+
+#[derive(Debug)]
+pub enum Node {
+    Group(Group),
+    Garbage(Garbage),
+}
+
+impl Node {
+    pub fn get_span(&self) -> Span {
+        match *self {
+            Node::Group(ref group) => group.span,
+            Node::Garbage(ref garbage) => garbage.span,
+        }
+    }
+}
+
+// Visits every node with its depth, where top-level nodes are at depth 1, in
+// the order they appear in the input.
+pub struct Nodes<'a> {
+    stack: Vec<(&'a Node, u64)>,
+}
+
+impl<'a> Iterator for Nodes<'a> {
+    type Item = (&'a Node, u64);
+
+    fn next(&mut self) -> Option<(&'a Node, u64)> {
+        let (node, depth) = self.stack.pop()?;
+        if let Node::Group(ref group) = *node {
+            self.stack.extend(group.children.iter().rev().map(|child| (child, depth + 1)));
+        }
+        Some((node, depth))
+    }
+}
+
+pub struct Stream {
+    pub nodes: Vec<Node>,
+}
+
+impl Stream {
+    pub fn iter(&self) -> Nodes<'_> {
+        Nodes { stack: self.nodes.iter().rev().map(|node| (node, 1)).collect() }
+    }
+
+    fn groups(&self) -> impl Iterator<Item = (&Group, u64)> {
+        self.iter().filter_map(|(node, depth)| match *node {
+            Node::Group(ref group) => Some((group, depth)),
+            Node::Garbage(_) => None,
+        })
+    }
+
+    fn garbage(&self) -> impl Iterator<Item = &Garbage> {
+        self.iter().filter_map(|(node, _)| match *node {
+            Node::Garbage(ref garbage) => Some(garbage),
+            Node::Group(_) => None,
+        })
+    }
+
+    pub fn get_score(&self) -> u64 {
+        self.groups().map(|(_, depth)| depth).sum()
+    }
+
+    pub fn get_garbage_chars(&self) -> usize {
+        self.garbage().map(|garbage| garbage.get_char_count()).sum()
+    }
+
+    // The first of the most deeply nested groups, with its depth.
+    pub fn get_deepest_group(&self) -> Option<(&Group, u64)> {
+        self.groups().fold(None, |deepest, (group, depth)| match deepest {
+            Some((_, deepest_depth)) if deepest_depth >= depth => deepest,
+            _ => Some((group, depth)),
+        })
+    }
+
+    // The first of the garbage blocks with the most countable characters.
+    pub fn get_largest_garbage(&self) -> Option<&Garbage> {
+        self.garbage().fold(None, |largest: Option<&Garbage>, garbage| match largest {
+            Some(largest) if largest.get_char_count() >= garbage.get_char_count() => Some(largest),
+            _ => Some(garbage),
+        })
+    }
+
+    // The innermost group whose span contains the offset.
+    pub fn get_group_at(&self, offset: usize) -> Option<(&Group, u64)> {
+        let mut found = None;
+        let mut nodes = &self.nodes;
+        let mut depth = 1;
+        while let Some(node) = nodes.iter().find(|node| node.get_span().contains(offset)) {
+            match *node {
+                Node::Group(ref group) => {
+                    found = Some((group, depth));
+                    nodes = &group.children;
+                    depth += 1;
+                },
+                Node::Garbage(_) => break,
+            }
+        }
+        found
+    }
+}
+
+fn problem(kind: ProblemKind, offset: usize, open: &[(usize, Vec<Node>)]) -> Problem {
+    Problem { kind, offset, depth: open.len(), enclosing_group: open.last().map(|&(start, _)| start) }
+}
+
+// Stops at the first problem which leaves the tree ill-formed. Stray
+// characters are ignored, so several top-level nodes are allowed.
+pub fn parse(input: &str) -> Result<Stream, Problem> {
+    // The groups still open, each with its start offset and children so far.
+    let mut open: Vec<(usize, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut garbage_start = None;
+    let mut cancelled_chars = 0;
+
+    for (token, offset) in token::tokenize(input) {
+        let node = match token {
+            Token::GroupStart => {
+                open.push((offset, Vec::new()));
+                continue;
+            },
+            Token::GroupEnd => match open.pop() {
+                Some((start, children)) => Node::Group(Group { children, span: Span { start, end: offset + 1 } }),
+                None => return Err(problem(ProblemKind::UnmatchedCloser, offset, &open)),
+            },
+            Token::GarbageStart => {
+                garbage_start = Some(offset);
+                cancelled_chars = 0;
+                continue;
+            },
+            Token::Cancel => {
+                cancelled_chars += 1;
+                continue;
+            },
+            Token::GarbageEnd => {
+                let start = garbage_start.take().unwrap();
+                let raw = input[start + 1..offset].to_string();
+                Node::Garbage(Garbage { raw, cancelled_chars, span: Span { start, end: offset + 1 } })
+            },
+            Token::Separator | Token::GarbageChar | Token::Stray => continue,
+        };
+
+        match open.last_mut() {
+            Some(&mut (_, ref mut children)) => children.push(node),
+            None => nodes.push(node),
+        }
+    }
+
+    if let Some(start) = garbage_start {
+        return Err(problem(ProblemKind::UnterminatedGarbage, start, &open));
+    }
+    if let Some((start, _)) = open.pop() {
+        return Err(problem(ProblemKind::UnclosedGroup, start, &open));
+    }
+    Ok(Stream { nodes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_groups_by_depth() {
+        let stream = parse("{{<a!>b>,{}},{<>},{<!!>}}").unwrap();
+        assert_eq!(stream.get_score(), 1 + 2 + 3 + 2 + 2);
+        assert_eq!(stream.get_garbage_chars(), 2);
+        assert_eq!(stream.get_deepest_group().map(|(group, depth)| (group.span, depth)), Some((Span { start: 9, end: 11 }, 3)));
+        assert_eq!(stream.get_group_at(3).map(|(group, depth)| (group.span, depth)), Some((Span { start: 1, end: 12 }, 2)));
+    }
+
+    #[test]
+    fn drops_deeply_nested_groups() {
+        let depth = 1_000_000;
+        let input = format!("{}{}", "{".repeat(depth), "}".repeat(depth));
+        let stream = parse(&input).unwrap();
+        assert_eq!(stream.get_deepest_group().map(|(_, depth)| depth), Some(depth as u64));
+        drop(stream);
+    }
+}